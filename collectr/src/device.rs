@@ -1,11 +1,14 @@
 use data_model::DeviceState::{New, Offline, Reporting, Stopped};
-use data_model::{DeviceState, MonitorReport, StateChange};
+use data_model::{DeviceState, MonitorReport, PathChange, StateChange};
 use std::borrow::Cow;
 use worker::durable_object;
 use worker::*;
 
 const MARGIN_SECONDS: u64 = 5;
 
+/// The maximum number of path changes kept for each device
+const MAX_PATH_CHANGES: usize = 100;
+
 pub const STATE_CHANGES_QUEUE: &str = "STATE_CHANGES";
 
 #[durable_object]
@@ -29,15 +32,22 @@ impl Device {
         &mut self,
         report_type: &str,
         period_seconds: Option<u64>,
-        _report: Option<MonitorReport>,
+        reason: Option<String>,
+        report: Option<MonitorReport>,
     ) -> Result<Response> {
         let timestamp = Date::now();
         console_log!(
-            "Event: {} TimeStamp: {}",
+            "Event: {} Reason: {:?} TimeStamp: {}",
             report_type,
+            reason,
             timestamp.to_string()
         );
 
+        if let Some(report) = &report {
+            self.record_path_changes(report, timestamp.as_millis())
+                .await?;
+        }
+
         // Note: `New` is not one of the possible states set below, so if this is the first time the DO for this
         // device runs it MUST result in a different state (Reporting would be normal, but others in error cases)
         // and so the new state (`New` not being one of them) MUST be stored and a state change event generated
@@ -50,22 +60,25 @@ impl Device {
                         .set_alarm(((period + MARGIN_SECONDS) * 1000) as i64)
                         .await?;
                 }
-                self.new_state(Reporting, timestamp.as_millis()).await?;
+                self.new_state(Reporting, timestamp.as_millis(), reason)
+                    .await?;
             }
             "stop" => {
                 // A Stop report was received
                 if self.device_state == Stopped {
                     console_warn!("Stop Report with device in Stopped state");
                 }
+                // e.g. a device being suspended sends a Stop report, so it is not considered Offline
                 self.state.storage().delete_alarm().await?;
-                self.new_state(Stopped, timestamp.as_millis()).await?;
+                self.new_state(Stopped, timestamp.as_millis(), reason)
+                    .await?;
             }
             _ => match &self.device_state {
                 // alarm was sent - so an expected report didn't arrive by the expected time
                 New => console_warn!("Report overdue with device in New state"),
                 Stopped => console_warn!("Report overdue with device in Stopped state"),
                 Offline => console_warn!("Report overdue with device in Offline state"),
-                Reporting => self.new_state(Offline, timestamp.as_millis()).await?,
+                Reporting => self.new_state(Offline, timestamp.as_millis(), None).await?,
             },
         }
 
@@ -79,7 +92,12 @@ impl Device {
 
     // change the state of the tracked device to the new state, if it is different from the current state
     // then store the state for use in future instances of this DurableObject
-    async fn new_state(&mut self, new_state: DeviceState, timestamp: u64) -> Result<()> {
+    async fn new_state(
+        &mut self,
+        new_state: DeviceState,
+        timestamp: u64,
+        reason: Option<String>,
+    ) -> Result<()> {
         if self.device_state != new_state {
             let id = &self.state.id().to_string();
 
@@ -101,6 +119,7 @@ impl Device {
                 state: self.device_state.clone(),
                 connection: self.connection.clone(),
                 timestamp,
+                reason,
             };
             queue.send(&state_change).await?;
         }
@@ -108,6 +127,59 @@ impl Device {
         Ok(())
     }
 
+    // Record a path change event for each path in the report that includes its hops (which the
+    // device only does when the path changed) and has a different fingerprint to the last one
+    async fn record_path_changes(&mut self, report: &MonitorReport, timestamp: u64) -> Result<()> {
+        for path in &report.paths {
+            let Some(hops) = &path.hops else { continue };
+            let key = format!("path::{}", path.target);
+            let previous_fingerprint: Option<String> = self.state.storage().get(&key).await.ok();
+            if previous_fingerprint.as_ref() == Some(&path.fingerprint) {
+                continue;
+            }
+
+            console_log!(
+                "Path to {} changed from {:?} to {}",
+                path.target,
+                previous_fingerprint,
+                path.fingerprint
+            );
+            self.state.storage().put(&key, &path.fingerprint).await?;
+
+            let mut changes: Vec<PathChange> = self
+                .state
+                .storage()
+                .get("path_changes")
+                .await
+                .unwrap_or_default();
+            changes.push(PathChange {
+                id: self.state.id().to_string(),
+                target: path.target.clone(),
+                previous_fingerprint,
+                fingerprint: path.fingerprint.clone(),
+                hops: hops.clone(),
+                timestamp,
+            });
+            if changes.len() > MAX_PATH_CHANGES {
+                changes.drain(..changes.len() - MAX_PATH_CHANGES);
+            }
+            self.state.storage().put("path_changes", &changes).await?;
+        }
+
+        Ok(())
+    }
+
+    // Respond with the path changes recorded for this device
+    async fn path_changes(&self) -> Result<Response> {
+        let changes: Vec<PathChange> = self
+            .state
+            .storage()
+            .get("path_changes")
+            .await
+            .unwrap_or_default();
+        Response::from_json(&changes)
+    }
+
     // Store the Device in the DO's storage
     async fn store(&mut self) {
         let _ = self
@@ -151,16 +223,21 @@ impl DurableObject for Device {
         console_log!("\nDO ID: {}", self.state.id().to_string());
 
         let path = req.path();
+        if path == "/paths" {
+            return self.path_changes().await;
+        }
         let report_type = path.split('/').nth(2).unwrap();
 
         self.load().await;
 
         let mut period = None;
+        let mut reason = None;
         let url = req.url().unwrap();
         for query_pair in url.query_pairs() {
             match query_pair.0 {
                 Cow::Borrowed("connection") => self.connection = Some(query_pair.1.to_string()),
                 Cow::Borrowed("period") => period = query_pair.1.parse::<u64>().ok(),
+                Cow::Borrowed("reason") => reason = Some(query_pair.1.to_string()),
                 _ => {}
             }
         }
@@ -175,7 +252,8 @@ impl DurableObject for Device {
                                 serde_json::from_str(&report_string);
                             match report_json {
                                 Ok(report) => {
-                                    self.process_report(report_type, period, Some(report)).await
+                                    self.process_report(report_type, period, reason, Some(report))
+                                        .await
                                 }
                                 Err(_) => Response::error("Could not deserialize report", 400),
                             }
@@ -185,7 +263,7 @@ impl DurableObject for Device {
                     _ => Response::error("Unexpected FormEntry in report FormData", 400),
                 }
             }
-            Method::Get => self.process_report(report_type, period, None).await,
+            Method::Get => self.process_report(report_type, period, reason, None).await,
             _ => Response::error("Unexpected HTTP Method used", 400),
         }
     }
@@ -194,6 +272,6 @@ impl DurableObject for Device {
     async fn alarm(&mut self) -> Result<Response> {
         console_log!("Alarm DO ID: {}", self.state.id().to_string());
         self.load().await;
-        self.process_report("alarm", None, None).await
+        self.process_report("alarm", None, None, None).await
     }
 }
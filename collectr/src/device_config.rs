@@ -0,0 +1,52 @@
+use std::borrow::Cow;
+
+use config::{fnv1a, RemoteSettings};
+use worker::*;
+
+/// KV namespace with the config for each device, in the same format as wimon's config file,
+/// keyed by device ID
+pub const DEVICE_CONFIG_KV_NAMESPACE: &str = "DEVICE_CONFIG";
+
+/// Key of the config for devices that don't have their own
+const DEFAULT_CONFIG_KEY: &str = "default";
+
+// Respond with the config for the device, or 404 if there is none (including when the KV
+// namespace is not bound, i.e. remote config is not set up). The response has an ETag,
+// so that a device can check for changes without being sent the config again each time.
+pub async fn device_config(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let url = req.url()?;
+    let device_id = url.query_pairs().find_map(|(key, value)| match key {
+        Cow::Borrowed("device_id") => Some(value.to_string()),
+        _ => None,
+    });
+    let Some(device_id) = device_id else {
+        return Response::error("Bad Request - missing device_id", 400);
+    };
+
+    let Ok(kv) = ctx.kv(DEVICE_CONFIG_KV_NAMESPACE) else {
+        return Response::error("No config for device", 404);
+    };
+    let text = match kv.get(&device_id).text().await? {
+        Some(text) => text,
+        None => match kv.get(DEFAULT_CONFIG_KEY).text().await? {
+            Some(text) => text,
+            None => return Response::error("No config for device", 404),
+        },
+    };
+
+    // The config crate's types are the schema, so a config stored with errors in it, or with
+    // settings that can only be set on the device, is never sent to devices
+    if let Err(e) = RemoteSettings::parse(url.as_str(), &text) {
+        console_error!("Invalid config for device {}: {}", device_id, e);
+        return Response::error(format!("Invalid config for device: {e}"), 500);
+    }
+
+    let etag = format!("\"{:016x}\"", fnv1a(text.as_bytes()));
+    let mut headers = Headers::new();
+    headers.set("ETag", &etag)?;
+    if req.headers().get("If-None-Match")?.as_ref() == Some(&etag) {
+        return Ok(Response::empty()?.with_status(304).with_headers(headers));
+    }
+    headers.set("Content-Type", "application/toml")?;
+    Ok(Response::ok(text)?.with_headers(headers))
+}
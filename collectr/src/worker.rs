@@ -4,6 +4,7 @@ use worker::*;
 use data_model::{DeviceDetails, StateChange};
 
 mod device;
+mod device_config;
 
 const DEVICE_STATUS_KV_NAMESPACE: &str = "DEVICE_STATUS";
 const DEVICE_DETAILS_KV_NAMESPACE: &str = "DEVICE_DETAILS";
@@ -47,6 +48,12 @@ async fn main(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         .post_async("/report/:type", |req, ctx| async move {
             device_report(req, ctx).await
         })
+        .get_async("/paths", |req, ctx| async move {
+            device_report(req, ctx).await
+        })
+        .get_async("/config", |req, ctx| async move {
+            device_config::device_config(req, ctx).await
+        })
         .run(req, env)
         .await
 }
@@ -0,0 +1,409 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+
+use serde::ser::{self, Impossible, Serialize};
+
+/// An error generating the Rust source of a value, e.g. because it has a map in it
+#[derive(Debug)]
+pub struct GenerateError(String);
+
+impl Display for GenerateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Could not generate the config source: {}", self.0)
+    }
+}
+
+impl Error for GenerateError {}
+
+impl ser::Error for GenerateError {
+    fn custom<T: Display>(msg: T) -> Self {
+        GenerateError(msg.to_string())
+    }
+}
+
+/// Generate a Rust expression that constructs `value` in the `no_std` model of the config, with
+/// text as string literals and lists as slices, so that it can be the value of a `const`. The
+/// types are qualified by `crate_path`, e.g. "::config".
+///
+/// The fields of the model that are skipped when serializing are not generated, so they must
+/// only be in the model with the "std" feature.
+pub fn to_rust<T: Serialize>(value: &T, crate_path: &str) -> Result<String, GenerateError> {
+    let mut generator = Generator {
+        source: String::new(),
+        crate_path,
+    };
+    value.serialize(&mut generator)?;
+    Ok(generator.source)
+}
+
+struct Generator<'a> {
+    source: String,
+    crate_path: &'a str,
+}
+
+impl Generator<'_> {
+    fn write(&mut self, text: &str) {
+        self.source.push_str(text);
+    }
+
+    // The path of a type, or of a variant of an enum, in the config crate
+    fn path(&mut self, names: &[&str]) {
+        self.source.push_str(self.crate_path);
+        for name in names {
+            self.source.push_str("::");
+            self.source.push_str(name);
+        }
+    }
+}
+
+impl ser::Serializer for &mut Generator<'_> {
+    type Ok = ();
+    type Error = GenerateError;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Impossible<(), GenerateError>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<(), GenerateError> {
+        self.write(&v.to_string());
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), GenerateError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), GenerateError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), GenerateError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), GenerateError> {
+        self.write(&v.to_string());
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), GenerateError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), GenerateError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), GenerateError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), GenerateError> {
+        self.write(&v.to_string());
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), GenerateError> {
+        self.write(&format!("{v:?}"));
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), GenerateError> {
+        self.write(&format!("{v:?}"));
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), GenerateError> {
+        self.write(&format!("{v:?}"));
+        Ok(())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), GenerateError> {
+        // The Debug format of a str is a valid string literal, with any characters escaped
+        self.write(&format!("{v:?}"));
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), GenerateError> {
+        self.write(&format!("&{v:?}"));
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), GenerateError> {
+        self.write("None");
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), GenerateError> {
+        self.write("Some(");
+        value.serialize(&mut *self)?;
+        self.write(")");
+        Ok(())
+    }
+
+    fn serialize_unit(self) -> Result<(), GenerateError> {
+        self.write("()");
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<(), GenerateError> {
+        self.path(&[name]);
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), GenerateError> {
+        self.path(&[name, variant]);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), GenerateError> {
+        self.path(&[name]);
+        self.write("(");
+        value.serialize(&mut *self)?;
+        self.write(")");
+        Ok(())
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), GenerateError> {
+        self.path(&[name, variant]);
+        self.write("(");
+        value.serialize(&mut *self)?;
+        self.write(")");
+        Ok(())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, GenerateError> {
+        self.write("&[");
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, GenerateError> {
+        self.write("(");
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Self, GenerateError> {
+        self.path(&[name]);
+        self.write("(");
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, GenerateError> {
+        self.path(&[name, variant]);
+        self.write("(");
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, GenerateError> {
+        Err(GenerateError(
+            "maps are not in the no_std model of the config".into(),
+        ))
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self, GenerateError> {
+        self.path(&[name]);
+        self.write(" { ");
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, GenerateError> {
+        self.path(&[name, variant]);
+        self.write(" { ");
+        Ok(self)
+    }
+}
+
+// The elements and fields are each followed by a comma, which Rust allows after the last one
+
+impl ser::SerializeSeq for &mut Generator<'_> {
+    type Ok = ();
+    type Error = GenerateError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), GenerateError> {
+        value.serialize(&mut **self)?;
+        self.write(", ");
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), GenerateError> {
+        self.write("]");
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for &mut Generator<'_> {
+    type Ok = ();
+    type Error = GenerateError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), GenerateError> {
+        value.serialize(&mut **self)?;
+        self.write(", ");
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), GenerateError> {
+        self.write(")");
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut Generator<'_> {
+    type Ok = ();
+    type Error = GenerateError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), GenerateError> {
+        value.serialize(&mut **self)?;
+        self.write(", ");
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), GenerateError> {
+        self.write(")");
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut Generator<'_> {
+    type Ok = ();
+    type Error = GenerateError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), GenerateError> {
+        value.serialize(&mut **self)?;
+        self.write(", ");
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), GenerateError> {
+        self.write(")");
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut Generator<'_> {
+    type Ok = ();
+    type Error = GenerateError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), GenerateError> {
+        self.write(key);
+        self.write(": ");
+        value.serialize(&mut **self)?;
+        self.write(", ");
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), GenerateError> {
+        self.write("}");
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut Generator<'_> {
+    type Ok = ();
+    type Error = GenerateError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), GenerateError> {
+        self.write(key);
+        self.write(": ");
+        value.serialize(&mut **self)?;
+        self.write(", ");
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), GenerateError> {
+        self.write("}");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::to_rust;
+    use crate::{Config, HookEvent, HookSpec, MonitorSpec, ReportSpec};
+
+    #[test]
+    fn config_as_rust() {
+        let config = Config {
+            monitor: Some(MonitorSpec::All),
+            report: Some(ReportSpec {
+                period_seconds: Some(60),
+                base_url: Some("https://example.com/\"pingr\"".into()),
+                no_proxy: Some(vec!["localhost".into()]),
+                ..ReportSpec::default()
+            }),
+            hooks: Some(vec![HookSpec {
+                event: HookEvent::SsidChanged,
+                command: "logger".into(),
+                args: None,
+                threshold: Some(3),
+                timeout_seconds: None,
+            }]),
+            ..Config::default()
+        };
+        let source = to_rust(&config, "::config").unwrap();
+        assert!(
+            source.starts_with("::config::Config { monitor: Some(::config::MonitorSpec::All), ")
+        );
+        assert!(source.contains(
+            "::config::ReportSpec { period_seconds: Some(60), \
+             base_url: Some(\"https://example.com/\\\"pingr\\\"\"), adaptive: None, "
+        ));
+        assert!(source.contains("no_proxy: Some(&[\"localhost\", ]), "));
+        assert!(source.contains(
+            "hooks: Some(&[::config::HookSpec { event: ::config::HookEvent::SsidChanged, \
+             command: \"logger\", args: None, threshold: Some(3), timeout_seconds: None, }, ]), "
+        ));
+        // The fields only in the std model are not generated
+        assert!(source.ends_with("data_dir: None, }"));
+    }
+
+    #[test]
+    fn maps_are_not_generated() {
+        let map = BTreeMap::from([("name", 1)]);
+        assert!(to_rust(&map, "::config").is_err());
+    }
+}
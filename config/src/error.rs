@@ -0,0 +1,188 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+
+use crate::Source;
+
+/// A position in a config file, counting from 1
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// The position of the byte at `offset` in `text`
+    pub(crate) fn at(text: &str, offset: usize) -> Self {
+        let before = &text[..offset.min(text.len())];
+        let line_start = before.rfind('\n').map(|index| index + 1).unwrap_or(0);
+        Position {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/// Errors reading a config file, with enough detail to find and fix the problem in the file.
+/// For a remote config the `path` is the URL it was fetched from.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read
+    Read { path: PathBuf, error: io::Error },
+    /// The file is not valid TOML, or a value is of the wrong type
+    Parse {
+        path: PathBuf,
+        position: Option<Position>,
+        message: String,
+    },
+    /// A key that is not a known setting, e.g. a misspelt one
+    UnknownKey {
+        path: PathBuf,
+        position: Option<Position>,
+        key: String,
+    },
+    /// A profile applied by an override that is not in `[profiles]`
+    UnknownProfile { path: PathBuf, profile: String },
+    /// A setting to override that is not one of [crate::SETTINGS]
+    UnknownSetting { setting: String, source: Source },
+    /// A value given to override a setting that is not valid for it
+    Override {
+        setting: String,
+        source: Source,
+        message: String,
+    },
+    /// A URL setting that is not a valid URL
+    InvalidUrl {
+        source: Source,
+        key: &'static str,
+        url: String,
+        error: url::ParseError,
+    },
+    /// `report.period_seconds` is zero
+    ZeroPeriod { source: Source },
+    /// A setting that is in the model but can't be used by this build, e.g. a TLS option that
+    /// rustls does not support
+    Unsupported {
+        source: Source,
+        setting: &'static str,
+        reason: &'static str,
+    },
+    /// A Wi-Fi network in the SSID file that could not be joined, e.g. one without a password
+    InvalidNetwork {
+        path: PathBuf,
+        ssid: String,
+        message: &'static str,
+    },
+}
+
+impl ConfigError {
+    /// Convert an error from parsing `text`, read from `path`
+    pub(crate) fn parse(path: PathBuf, text: &str, error: toml::de::Error) -> Self {
+        let position = error.span().map(|span| Position::at(text, span.start));
+        // serde reports unknown keys as e.g. "unknown field `perod_seconds`, expected one of ..."
+        let unknown_key = error
+            .message()
+            .strip_prefix("unknown field `")
+            .and_then(|rest| rest.split_once('`'))
+            .map(|(key, _)| key.to_string());
+        match unknown_key {
+            Some(key) => ConfigError::UnknownKey {
+                path,
+                position,
+                key,
+            },
+            None => ConfigError::Parse {
+                path,
+                position,
+                message: error.message().trim().to_string(),
+            },
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Read { path, error } => {
+                write!(f, "'{}': could not read file: {error}", path.display())
+            }
+            ConfigError::Parse {
+                path,
+                position,
+                message,
+            } => write!(f, "'{}'{}: {message}", path.display(), at(position)),
+            ConfigError::UnknownKey {
+                path,
+                position,
+                key,
+            } => write!(
+                f,
+                "'{}'{}: unknown key '{key}'",
+                path.display(),
+                at(position)
+            ),
+            ConfigError::UnknownProfile { path, profile } => {
+                write!(f, "'{}': unknown profile '{profile}'", path.display())
+            }
+            ConfigError::UnknownSetting { setting, source } => {
+                write!(f, "{source}: unknown setting '{setting}'")
+            }
+            ConfigError::Override {
+                setting,
+                source,
+                message,
+            } => write!(f, "{source}: invalid value for '{setting}': {message}"),
+            ConfigError::InvalidUrl {
+                source,
+                key,
+                url,
+                error,
+            } => write!(f, "{source}: '{key}' is not a valid URL ('{url}'): {error}"),
+            ConfigError::ZeroPeriod { source } => {
+                write!(
+                    f,
+                    "{source}: 'report.period_seconds' must be greater than zero"
+                )
+            }
+            ConfigError::Unsupported {
+                source,
+                setting,
+                reason,
+            } => write!(f, "{source}: '{setting}' is not supported: {reason}"),
+            ConfigError::InvalidNetwork {
+                path,
+                ssid,
+                message,
+            } => write!(f, "'{}': Wi-Fi network '{ssid}' {message}", path.display()),
+        }
+    }
+}
+
+// The position of an error in a file, if known, as " line L, column C"
+fn at(position: &Option<Position>) -> String {
+    position
+        .map(|position| format!(" line {}, column {}", position.line, position.column))
+        .unwrap_or_default()
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Read { error, .. } => Some(error),
+            ConfigError::InvalidUrl { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// So that config errors can be returned where other errors are [io::Error]s, keeping the
+/// detail in the message
+impl From<ConfigError> for io::Error {
+    fn from(error: ConfigError) -> Self {
+        let kind = match &error {
+            ConfigError::Read { error, .. } => error.kind(),
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, error.to_string())
+    }
+}
@@ -0,0 +1,193 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, io};
+
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{load_config, Config, ConfigError, NetworkSpec, Source, WifiSecurity};
+
+pub fn find_config_file(file_name: &str) -> Result<PathBuf, io::Error> {
+    let mut dir = env::current_dir().ok();
+
+    // Loop until no parent director exists. (i.e. stop at "/")
+    while let Some(directory) = dir {
+        let config_path = directory.join(file_name);
+
+        if config_path.exists() {
+            return Ok(config_path);
+        }
+
+        dir = directory.parent().map(|p| p.to_path_buf());
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "wimon toml config file not found",
+    ))
+}
+
+/// Read the config from a file, with any overrides of its settings from `PINGR_*` environment
+/// variables. See [load_config]
+pub fn read_config(config_file_path: &PathBuf) -> Result<Config, ConfigError> {
+    Ok(load_config(Some(config_file_path), &[])?.config)
+}
+
+// Set the values derived from the settings, resolving relative paths against the config file's
+// directory. `source` gives where a setting came from, for errors.
+pub(crate) fn resolve(
+    config: &mut Config,
+    config_dir: &Path,
+    source: impl Fn(&str) -> Source,
+) -> Result<(), ConfigError> {
+    config.period_duration = match config.report.as_ref().and_then(|spec| spec.period_seconds) {
+        Some(0) => {
+            return Err(ConfigError::ZeroPeriod {
+                source: source("report.period_seconds"),
+            })
+        }
+        Some(period) => Duration::from_secs(period),
+        None => Duration::from_secs(60),
+    };
+
+    // curl is built with rustls, which can't pin public keys or load encrypted keys. Using
+    // these would make every report fail, so refuse them up front
+    if let Some(tls) = config.report.as_ref().and_then(|spec| spec.tls.as_ref()) {
+        if tls.pinned_public_keys.is_some() {
+            return Err(ConfigError::Unsupported {
+                source: source("report.tls.pinned_public_keys"),
+                setting: "report.tls.pinned_public_keys",
+                reason: "the TLS library used (rustls) can't pin public keys",
+            });
+        }
+        if tls.client_key_password.is_some() {
+            return Err(ConfigError::Unsupported {
+                source: source("report.tls.client_key_password"),
+                setting: "report.tls.client_key_password",
+                reason: "the TLS library used (rustls) can't load encrypted keys, \
+                    give an unencrypted 'client_key'",
+            });
+        }
+    }
+
+    // A bad URL is an error, rather than silently not reporting to the collector
+    config.report_url = match config
+        .report
+        .as_ref()
+        .and_then(|spec| spec.base_url.as_ref())
+    {
+        Some(url_string) => {
+            Some(
+                Url::parse(url_string).map_err(|error| ConfigError::InvalidUrl {
+                    source: source("report.base_url"),
+                    key: "report.base_url",
+                    url: url_string.clone(),
+                    error,
+                })?,
+            )
+        }
+        None => None,
+    };
+
+    config.remote_url = match config.remote.as_ref() {
+        Some(spec) if spec.enabled.unwrap_or(true) => match &spec.url {
+            Some(url_string) => {
+                Some(
+                    Url::parse(url_string).map_err(|error| ConfigError::InvalidUrl {
+                        source: source("remote.url"),
+                        key: "remote.url",
+                        url: url_string.clone(),
+                        error,
+                    })?,
+                )
+            }
+            None => config
+                .report_url
+                .as_ref()
+                .and_then(|url| url.join("config").ok()),
+        },
+        _ => None,
+    };
+
+    if let Some(simulation) = config.simulation.as_mut() {
+        simulation.timeline_file = config_dir
+            .join(&simulation.timeline_file)
+            .to_string_lossy()
+            .into_owned();
+    }
+    config.data_path = match &config.data_dir {
+        Some(dir) => config_dir.join(dir),
+        None => config_dir.to_path_buf(),
+    };
+
+    Ok(())
+}
+
+/// The Wi-Fi networks for picomon to join, from its SSID file
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SsidSpec {
+    /// A network to try before `networks`, which `configr` can change in a built binary. It is
+    /// an open network if it has no password
+    pub ssid_name: Option<String>,
+    pub ssid_pass: Option<String>,
+    /// Networks to join, in order of preference
+    pub networks: Option<Vec<NetworkSpec>>,
+}
+
+pub fn read_ssid(ssid_file_path: &PathBuf) -> Result<SsidSpec, ConfigError> {
+    let spec: SsidSpec = read_toml(ssid_file_path)?;
+    let marked = spec
+        .ssid_name
+        .iter()
+        .filter(|ssid| !ssid.is_empty())
+        .map(|ssid| {
+            let password = spec.ssid_pass.clone().filter(|pass| !pass.is_empty());
+            let security = match password {
+                Some(_) => WifiSecurity::Wpa2,
+                None => WifiSecurity::Open,
+            };
+            NetworkSpec {
+                ssid: ssid.clone(),
+                password,
+                security: Some(security),
+            }
+        });
+    for network in marked.chain(spec.networks.iter().flatten().cloned()) {
+        if let Some(message) = network.problem() {
+            return Err(ConfigError::InvalidNetwork {
+                path: ssid_file_path.clone(),
+                ssid: network.ssid,
+                message,
+            });
+        }
+    }
+    Ok(spec)
+}
+
+impl NetworkSpec {
+    /// Why the network could not be joined, if it could not, given the limits of SSIDs and
+    /// WPA passphrases
+    pub fn problem(&self) -> Option<&'static str> {
+        match (self.security.unwrap_or_default(), &self.password) {
+            _ if self.ssid.is_empty() || self.ssid.len() > 32 => {
+                Some("must have an SSID of 1 to 32 bytes")
+            }
+            (WifiSecurity::Wpa3, _) => Some("uses WPA3, which picomon can't join yet"),
+            (WifiSecurity::Open, None) => None,
+            (WifiSecurity::Open, Some(_)) => Some("is open, so can't have a password"),
+            (_, Some(password)) if (8..=63).contains(&password.len()) => None,
+            (_, _) => Some("needs a password of 8 to 63 characters"),
+        }
+    }
+}
+
+// Read and parse a toml file, with the position of any error in it
+pub(crate) fn read_toml<T: DeserializeOwned>(path: &PathBuf) -> Result<T, ConfigError> {
+    let text = std::fs::read_to_string(path).map_err(|error| ConfigError::Read {
+        path: path.clone(),
+        error,
+    })?;
+    toml::from_str(&text).map_err(|error| ConfigError::parse(path.clone(), &text, error))
+}
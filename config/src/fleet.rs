@@ -0,0 +1,174 @@
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+use crate::layers::{leaves, Settings};
+use crate::{Config, ConfigError, OverrideSpec, Source};
+
+/// Who a device is, used to choose which `[[overrides]]` in a fleet's config file apply to it
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Identity {
+    pub device_id: Option<String>,
+    pub hostname: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl Identity {
+    /// The identity given by the `[device]` settings: the `id` and the `tags`
+    pub fn of(config: &Config) -> Self {
+        let device = config.device.as_ref();
+        Identity {
+            device_id: device.and_then(|device| device.id.clone()),
+            hostname: None,
+            tags: device
+                .and_then(|device| device.tags.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl OverrideSpec {
+    /// Whether the override applies to a device. All of the `device_id`, `hostname` and `tag`
+    /// given must match, so an override with none of them applies to every device.
+    pub fn applies_to(&self, identity: &Identity) -> bool {
+        self.device_id
+            .as_ref()
+            .is_none_or(|id| identity.device_id.as_ref() == Some(id))
+            && self.hostname.as_ref().is_none_or(|hostname| {
+                identity
+                    .hostname
+                    .as_ref()
+                    .is_some_and(|host| host.eq_ignore_ascii_case(hostname))
+            })
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|tag| identity.tags.contains(tag))
+    }
+}
+
+/// The `[profiles]` and `[[overrides]]` sections of a config file shared by a fleet of devices
+#[derive(Default)]
+pub(crate) struct Fleet {
+    path: PathBuf,
+    profiles: Table,
+    overrides: Vec<(OverrideSpec, Table)>,
+}
+
+impl Fleet {
+    /// Take the fleet sections out of a config file's `table`. `config` is the same file read
+    /// as a [Config], so has already been checked for errors.
+    pub fn take(path: &Path, config: Config, table: &mut Table) -> Self {
+        let profiles = match table.remove("profiles") {
+            Some(Value::Table(profiles)) => profiles,
+            _ => Table::new(),
+        };
+        let tables = match table.remove("overrides") {
+            Some(Value::Array(overrides)) => overrides,
+            _ => vec![],
+        };
+        let overrides = config
+            .overrides
+            .unwrap_or_default()
+            .into_iter()
+            .zip(tables)
+            .map(|(spec, table)| match table {
+                Value::Table(table) => (spec, table),
+                _ => (spec, Table::new()),
+            })
+            .collect();
+
+        Fleet {
+            path: path.to_path_buf(),
+            profiles,
+            overrides,
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty()
+    }
+
+    /// The settings to apply to the device with `identity`, from each of the profiles and
+    /// overrides that apply to it, in order
+    pub fn settings_for(
+        &self,
+        identity: &Identity,
+    ) -> Result<Vec<(Source, Settings)>, ConfigError> {
+        let mut layers = vec![];
+        for (index, (spec, table)) in self.overrides.iter().enumerate() {
+            if !spec.applies_to(identity) {
+                continue;
+            }
+            for name in spec.profiles.iter().flatten() {
+                let source = Source::Profile(self.path.clone(), name.clone());
+                let profile = match self.profiles.get(name) {
+                    Some(Value::Table(profile)) => profile,
+                    _ => {
+                        return Err(ConfigError::UnknownProfile {
+                            path: self.path.clone(),
+                            profile: name.clone(),
+                        })
+                    }
+                };
+                layers.push((source.clone(), settings(profile, source)?));
+            }
+            if let Some(Value::Table(overrides)) = table.get("settings") {
+                let source = Source::DeviceOverride(self.path.clone(), index + 1);
+                layers.push((source.clone(), settings(overrides, source)?));
+            }
+        }
+        Ok(layers)
+    }
+}
+
+// The settings in a profile or override, which can't have profiles or overrides of their own
+fn settings(table: &Table, source: Source) -> Result<Settings, ConfigError> {
+    if let Some(nested) = ["profiles", "overrides"]
+        .into_iter()
+        .find(|key| table.contains_key(*key))
+    {
+        return Err(ConfigError::Override {
+            setting: nested.to_string(),
+            source,
+            message: "can only be at the top level of the config file".into(),
+        });
+    }
+    let mut settings = vec![];
+    leaves(table, "", &mut settings);
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Identity;
+    use crate::OverrideSpec;
+
+    #[test]
+    fn overrides_apply_when_all_given_match() {
+        let device = Identity {
+            device_id: Some("abc123".into()),
+            hostname: Some("pi-warehouse-1".into()),
+            tags: vec!["warehouse".into(), "north".into()],
+        };
+        let by_tag = OverrideSpec {
+            tag: Some("warehouse".into()),
+            ..Default::default()
+        };
+        assert!(by_tag.applies_to(&device));
+        assert!(!by_tag.applies_to(&Identity::default()));
+
+        let by_host_and_tag = OverrideSpec {
+            hostname: Some("PI-WAREHOUSE-1".into()),
+            tag: Some("south".into()),
+            ..Default::default()
+        };
+        assert!(!by_host_and_tag.applies_to(&device));
+
+        assert!(OverrideSpec::default().applies_to(&Identity::default()));
+    }
+}
@@ -0,0 +1,45 @@
+use core::time::Duration;
+
+/// A (FNV-1a) hash of some data, which unlike std's hashers is stable across runs and platforms,
+/// so wimon, picomon and collectr all get the same hash of the same data
+pub fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// A fixed offset within `period` for a device, derived from a hash of its ID, used to spread
+/// the reports of devices that start together over the period. Whole seconds, as picomon
+/// schedules in seconds.
+pub fn phase_offset(device_id: &[u8], period: Duration) -> Duration {
+    match period.as_secs() {
+        0 => Duration::ZERO,
+        seconds => Duration::from_secs(fnv1a(device_id) % seconds),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::time::Duration;
+
+    use super::{fnv1a, phase_offset};
+
+    #[test]
+    fn fnv1a_is_stable() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn phase_offset_is_stable_and_within_period() {
+        let period = Duration::from_secs(60);
+        let id = b"c6426011b76adc13";
+        assert_eq!(phase_offset(id, period), phase_offset(id, period));
+        assert!(phase_offset(id, period) < period);
+        assert_ne!(
+            phase_offset(id, period),
+            phase_offset(b"c6426011b76adc14", period)
+        );
+        assert_eq!(phase_offset(id, Duration::ZERO), Duration::ZERO);
+    }
+}
@@ -0,0 +1,686 @@
+//! The config of a monitor: wimon, or picomon on a Raspberry Pi Pico W.
+//!
+//! The model of the config is shared by them. With the "std" feature (the default) it is read
+//! from a TOML config file, with its text and lists owned. Without it the crate is `no_std`, the
+//! text and lists are `&'static`, so that a config can be a `const` in firmware, generated from
+//! a config file at build time by [to_rust].
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+#[cfg(feature = "std")]
+use std::time::Duration;
+
+#[cfg(feature = "std")]
+use serde_derive::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use url::Url;
+
+#[cfg(feature = "std")]
+pub use codegen::{to_rust, GenerateError};
+#[cfg(feature = "std")]
+pub use error::{ConfigError, Position};
+#[cfg(feature = "std")]
+pub use file::{find_config_file, read_config, read_ssid, SsidSpec};
+#[cfg(feature = "std")]
+pub use fleet::Identity;
+pub use hash::{fnv1a, phase_offset};
+#[cfg(feature = "std")]
+pub use layers::{
+    env_name, load_config, load_config_as, LayeredConfig, Source, CONFIG_FILE_ENV, ENV_PREFIX,
+};
+#[cfg(feature = "std")]
+pub use remote::{RemoteSettings, LOCAL_SETTINGS};
+#[cfg(feature = "std")]
+pub use settings::SETTINGS;
+#[cfg(feature = "std")]
+pub(crate) use file::{read_toml, resolve};
+
+#[cfg(feature = "std")]
+mod codegen;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
+mod file;
+#[cfg(feature = "std")]
+mod fleet;
+mod hash;
+#[cfg(feature = "std")]
+mod layers;
+#[cfg(feature = "std")]
+mod remote;
+#[cfg(feature = "std")]
+mod settings;
+
+/// Text in the config
+#[cfg(feature = "std")]
+pub type Str = String;
+/// Text in the config
+#[cfg(not(feature = "std"))]
+pub type Str = &'static str;
+
+/// A list of values in the config
+#[cfg(feature = "std")]
+pub type List<T> = Vec<T>;
+/// A list of values in the config
+#[cfg(not(feature = "std"))]
+pub type List<T> = &'static [T];
+
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum MonitorSpec {
+    /// Report status of all SSIDs that are detected at each monitoring moment
+    All,
+    /// Only report on the status of the connection (wifi or ethernet) used to send results
+    #[default]
+    Connection,
+}
+
+/// Adapt the report period to the health of the connection: shorter while it is degraded,
+/// to get finer-grained data, and longer while it is healthy, to reduce traffic
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct AdaptiveSpec {
+    /// Shortest period used while degraded. Default: a quarter of `period_seconds`
+    pub min_period_seconds: Option<u64>,
+    /// Longest period used while healthy. Default: four times `period_seconds`
+    pub max_period_seconds: Option<u64>,
+    /// The connection is degraded if its signal level (in dBm) is below this
+    pub min_signal_dbm: Option<i16>,
+}
+
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct ReportSpec {
+    pub period_seconds: Option<u64>,
+    pub base_url: Option<Str>,
+    pub adaptive: Option<AdaptiveSpec>,
+    /// Delay the first report by a random time of up to this many seconds, so that devices
+    /// that start at the same time (e.g. after a power cut) don't all report together. Default: 0
+    pub startup_jitter_seconds: Option<u64>,
+    /// Offset the report schedule within the period by an amount derived from the device ID,
+    /// so that devices keep reporting at different times. Default: false
+    pub phase_offset: Option<bool>,
+    /// Proxy to send reports through, e.g. "http://proxy:3128" or "socks5h://proxy:1080"
+    pub proxy: Option<Str>,
+    /// Hosts to connect to directly, not through the proxy
+    pub no_proxy: Option<List<Str>>,
+    pub tls: Option<TlsSpec>,
+}
+
+/// TLS options used when sending reports to an https `base_url`
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct TlsSpec {
+    /// PEM files of CA certificates to trust, in addition to the system's
+    pub ca_files: Option<List<Str>>,
+    /// Public keys the server must have one of, as "sha256//{base64 hash}". Not supported yet:
+    /// reports are sent with rustls, which can't pin keys, so setting this is an error
+    pub pinned_public_keys: Option<List<Str>>,
+    /// PEM client certificate and unencrypted key, for mutual TLS
+    pub client_cert: Option<Str>,
+    pub client_key: Option<Str>,
+    /// Password of an encrypted `client_key`. Not supported yet, as rustls can't load
+    /// encrypted keys, so setting this is an error
+    pub client_key_password: Option<Str>,
+}
+
+/// Probes made when measuring, to check connectivity beyond the local link
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct ProbeSpec {
+    /// "host:port" targets to make a TCP connection to, e.g. "1.1.1.1:53"
+    pub targets: Option<List<Str>>,
+    /// Timeout for each probe, in milliseconds. Default: 2000
+    pub timeout_ms: Option<u64>,
+    /// Interfaces to also run the probes through, each bound to the interface by name
+    /// (e.g. "eth0", Linux only) or by source address (e.g. "192.168.1.10")
+    pub interfaces: Option<List<Str>>,
+}
+
+/// Track the addressing of the device: local addresses, gateway, DNS servers, DHCP lease and
+/// public IP, so that changes (e.g. a new public IP from the ISP) are reported
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct AddressingSpec {
+    /// Report the addressing. Default: true, if the section is present
+    pub enabled: Option<bool>,
+    /// URL of an endpoint that responds with the public IP of the requester as plain text,
+    /// e.g. "https://api.ipify.org". The public IP is not reported if not set
+    pub public_ip_url: Option<Str>,
+}
+
+/// Replay a scripted timeline of measurements and outages from a file, instead of measuring
+/// the real network, for testing without Wi-Fi hardware
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct SimulationSpec {
+    /// The timeline file. Relative paths are relative to the config file's directory
+    pub timeline_file: Str,
+}
+
+/// The protocol used to trace paths
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum TraceProtocol {
+    #[default]
+    Udp,
+    /// ICMP echo requests, which may need root privileges
+    Icmp,
+}
+
+/// Trace the network path (traceroute-style) to some targets, to detect route changes
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct TraceSpec {
+    /// Hosts to trace the path to, e.g. "1.1.1.1"
+    pub targets: Option<List<Str>>,
+    /// Trace the paths every this many report periods. Default: 10
+    pub every_periods: Option<u32>,
+    /// Maximum number of hops (TTL) traced. Default: 30
+    pub max_hops: Option<u8>,
+    /// Time to wait for a reply from each hop, in milliseconds. Default: 1000
+    pub timeout_ms: Option<u64>,
+    /// Default: Udp
+    pub protocol: Option<TraceProtocol>,
+}
+
+/// Sample the signal level and probe latencies several times during each report period, and
+/// report statistics of them, so that short fades are not missed
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct SamplingSpec {
+    /// Time between samples. The last sample in a period is taken when the report is made
+    pub interval_seconds: Option<u64>,
+}
+
+/// Connectivity events that hooks can be run on
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum HookEvent {
+    /// Sending reports has failed `threshold` times in a row
+    ReportFailures,
+    /// A report was sent after `threshold` or more failures in a row
+    ReportRecovered,
+    /// The SSID (or connection) used has changed
+    SsidChanged,
+    /// A probe failed, after succeeding
+    ProbeFailed,
+    /// A probe succeeded, after failing
+    ProbeRecovered,
+    /// The local addresses, gateway, DNS servers, DHCP lease or public IP changed
+    AddressingChanged,
+}
+
+/// A local command to run when a connectivity event happens, e.g. to restart a modem
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct HookSpec {
+    pub event: HookEvent,
+    pub command: Str,
+    pub args: Option<List<Str>>,
+    /// Number of consecutive report failures for `ReportFailures` and `ReportRecovered`. Default: 3
+    pub threshold: Option<u32>,
+    /// The command is killed if it runs for longer than this. Default: 30
+    pub timeout_seconds: Option<u64>,
+}
+
+/// Whether the service is installed for the whole system or just for the installing user
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum ServiceLevel {
+    System,
+    User,
+}
+
+/// systemd sandboxing options to add to the generated unit (Linux only)
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct SandboxSpec {
+    /// Prevent the service and its children from gaining new privileges. Default: true
+    pub no_new_privileges: Option<bool>,
+    /// Value for systemd's `ProtectSystem=` ("strict", "full" or "true"). Default: "strict"
+    pub protect_system: Option<Str>,
+    /// Value for systemd's `ProtectHome=` ("true", "read-only" or "tmpfs"). Default: "read-only"
+    pub protect_home: Option<Str>,
+    /// Give the service its own private /tmp. Default: true
+    pub private_tmp: Option<bool>,
+    /// Additional paths the service may write to, on top of its working directory
+    pub read_write_paths: Option<List<Str>>,
+}
+
+/// Options used when installing wimon as a service
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct ServiceSpec {
+    /// Install as a system or a user level service. If not set the platform default is used
+    pub level: Option<ServiceLevel>,
+    /// An existing user that the service should run as
+    pub username: Option<Str>,
+    /// Directory the service runs in. Defaults to the directory where the executable is
+    pub working_directory: Option<Str>,
+    /// Environment variables for the service process, each one as "NAME=value"
+    pub environment: Option<List<Str>>,
+    /// File with the complete platform-specific service definition to install, used as is
+    pub contents_file: Option<Str>,
+    /// Generate a systemd unit with these sandboxing options (ignored if `contents_file` is set)
+    pub sandbox: Option<SandboxSpec>,
+}
+
+/// How the ID that identifies this device to the collector is determined
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum IdStrategy {
+    /// A hash of hardware identifiers (CPU ID and System ID)
+    Hardware,
+    /// The MAC address of the primary network interface
+    Mac,
+    /// A random UUID generated the first time wimon runs
+    Random,
+    /// The `id` value in the `[device]` section of the config
+    Explicit,
+}
+
+/// How this device identifies itself when reporting
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct DeviceSpec {
+    /// Strategy used to determine the device ID. Default: Hardware
+    pub id_strategy: Option<IdStrategy>,
+    /// The device ID to use with the `Explicit` strategy
+    pub id: Option<Str>,
+    /// Tags used to choose which `[[overrides]]` apply to this device, e.g. ["warehouse"].
+    /// Usually set by an environment variable or on the command line, not in a shared file
+    pub tags: Option<List<Str>>,
+}
+
+/// The local history of measurements kept by wimon
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct HistorySpec {
+    /// Keep a history of measurements in the data directory. Default: true
+    pub enabled: Option<bool>,
+    /// Number of days measurements are kept for. Default: 30
+    pub retention_days: Option<u64>,
+}
+
+/// Pull settings for this device from the collector, to manage the config of a fleet centrally.
+/// They override the config file's settings, but not environment variables or the command line
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct RemoteSpec {
+    /// Use the remote config. Default: true, if the section is present
+    pub enabled: Option<bool>,
+    /// URL of the config endpoint. Default: "config" relative to the report `base_url`
+    pub url: Option<Str>,
+    /// Time between checks for a changed config. Default: 300
+    pub poll_seconds: Option<u64>,
+}
+
+/// The security of a Wi-Fi network
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum WifiSecurity {
+    #[default]
+    Wpa2,
+    /// Not supported by picomon yet, as its Wi-Fi firmware can't join WPA3 networks
+    Wpa3,
+    /// A network without a password
+    Open,
+}
+
+/// A Wi-Fi network that picomon can join
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct NetworkSpec {
+    pub ssid: Str,
+    /// Not used for an `Open` network
+    pub password: Option<Str>,
+    /// Default: Wpa2
+    pub security: Option<WifiSecurity>,
+}
+
+/// Settings for the devices that match all of the `device_id`, `hostname` and `tag` given. The
+/// profiles are applied first, in order, then the settings. Overrides are applied in the order
+/// they are in the file, so later ones take precedence.
+#[cfg(feature = "std")]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct OverrideSpec {
+    pub device_id: Option<String>,
+    pub hostname: Option<String>,
+    pub tag: Option<String>,
+    /// Names of profiles (in `[profiles]`) to apply
+    pub profiles: Option<Vec<String>>,
+    /// Settings to apply, as in the rest of the config file
+    pub settings: Option<Box<Config>>,
+}
+
+/// The config of a monitor. With the "std" feature it is read from a config file by
+/// [load_config], and the values derived from the settings are set. Without it, it is made
+/// by a `const` generated by [to_rust], e.g. in picomon's build script.
+#[derive(Default, Debug, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct Config {
+    pub monitor: Option<MonitorSpec>,
+    pub report: Option<ReportSpec>,
+    pub probe: Option<ProbeSpec>,
+    pub trace: Option<TraceSpec>,
+    pub addressing: Option<AddressingSpec>,
+    pub simulation: Option<SimulationSpec>,
+    pub sampling: Option<SamplingSpec>,
+    pub hooks: Option<List<HookSpec>>,
+    pub service: Option<ServiceSpec>,
+    pub device: Option<DeviceSpec>,
+    pub history: Option<HistorySpec>,
+    pub remote: Option<RemoteSpec>,
+    /// Wi-Fi networks for picomon to join, in order of preference. They are not read from the
+    /// config file, but from picomon's SSID file by its build, to keep the passwords out of it
+    #[cfg_attr(feature = "std", serde(skip_deserializing))]
+    pub networks: Option<List<NetworkSpec>>,
+    /// Directory where wimon keeps its state. Relative paths are relative to the config file's
+    /// directory, which is also the default
+    pub data_dir: Option<Str>,
+    /// Named sets of settings, for `overrides` to apply to some devices of a fleet. The profiles
+    /// and overrides are applied when loading the config, so they are not in the `no_std` model
+    #[cfg(feature = "std")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profiles: Option<BTreeMap<String, Config>>,
+    /// Settings for some devices of a fleet that share the config file
+    #[cfg(feature = "std")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overrides: Option<Vec<OverrideSpec>>,
+    #[cfg(feature = "std")]
+    #[serde(skip)]
+    pub period_duration: Duration,
+    #[cfg(feature = "std")]
+    #[serde(skip)]
+    pub report_url: Option<Url>,
+    #[cfg(feature = "std")]
+    #[serde(skip)]
+    pub data_path: PathBuf,
+    /// The URL of the remote config endpoint, if the remote config is enabled
+    #[cfg(feature = "std")]
+    #[serde(skip)]
+    pub remote_url: Option<Url>,
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::{
+        read_config, read_ssid, Config, ConfigError, HookEvent, MonitorSpec, Position,
+        ServiceLevel, WifiSecurity,
+    };
+
+    // Write a config file for the test `name`, returning its path. Shared by the tests of the
+    // other modules.
+    pub(crate) fn config_file(name: &str, contents: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("config-{name}-{}.toml", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn config_monitor_connection() {
+        let config: Config = toml::from_str("monitor = \"Connection\"\n").unwrap();
+        assert_eq!(config.monitor, Some(MonitorSpec::Connection));
+    }
+
+    #[test]
+    fn config_monitor_all() {
+        let config: Config = toml::from_str("monitor=\"All\"\n").unwrap();
+        assert_eq!(config.monitor, Some(MonitorSpec::All))
+    }
+
+    #[test]
+    fn config_with_report_spec() {
+        let config: Config = toml::from_str("[report]\nperiod_seconds = 1\n").unwrap();
+        assert_eq!(config.report.unwrap().period_seconds, Some(1));
+    }
+
+    #[test]
+    fn config_with_service_spec() {
+        let config: Config = toml::from_str(
+            "[service]\nlevel = \"System\"\nusername = \"wimon\"\n[service.sandbox]\nprivate_tmp = false\n",
+        )
+        .unwrap();
+        let service = config.service.unwrap();
+        assert_eq!(service.level, Some(ServiceLevel::System));
+        assert_eq!(service.username, Some("wimon".to_string()));
+        assert_eq!(service.sandbox.unwrap().private_tmp, Some(false));
+    }
+
+    #[test]
+    fn config_with_adaptive_report() {
+        let config: Config = toml::from_str(
+            "[report]\nperiod_seconds = 60\n[report.adaptive]\nmin_period_seconds = 10\nmin_signal_dbm = -75\n",
+        )
+        .unwrap();
+        let adaptive = config.report.unwrap().adaptive.unwrap();
+        assert_eq!(adaptive.min_period_seconds, Some(10));
+        assert_eq!(adaptive.max_period_seconds, None);
+        assert_eq!(adaptive.min_signal_dbm, Some(-75));
+    }
+
+    #[test]
+    fn config_with_hooks() {
+        let config: Config = toml::from_str(
+            "[[hooks]]\nevent = \"ReportFailures\"\ncommand = \"/usr/local/bin/restart-modem\"\nthreshold = 5\n\
+            [[hooks]]\nevent = \"SsidChanged\"\ncommand = \"logger\"\nargs = [\"-t\", \"wimon\"]\n",
+        )
+        .unwrap();
+        let hooks = config.hooks.unwrap();
+        assert_eq!(hooks.len(), 2);
+        assert_eq!(hooks[0].event, HookEvent::ReportFailures);
+        assert_eq!(hooks[0].threshold, Some(5));
+        assert_eq!(hooks[1].args, Some(vec!["-t".to_string(), "wimon".to_string()]));
+    }
+
+    #[test]
+    fn config_with_proxy_and_tls() {
+        let config: Config = toml::from_str(
+            "[report]\nproxy = \"socks5h://proxy:1080\"\n[report.tls]\nca_files = [\"/etc/wimon/ca.pem\"]\nclient_cert = \"/etc/wimon/client.pem\"\n",
+        )
+        .unwrap();
+        let report = config.report.unwrap();
+        assert_eq!(report.proxy, Some("socks5h://proxy:1080".to_string()));
+        let tls = report.tls.unwrap();
+        assert_eq!(tls.ca_files, Some(vec!["/etc/wimon/ca.pem".to_string()]));
+        assert_eq!(tls.client_key, None);
+    }
+
+    #[test]
+    fn unknown_key_is_an_error() {
+        let path = config_file("unknown", "[report]\nperiod_seconds = 60\nperod_seconds = 30\n");
+        match read_config(&path) {
+            Err(ConfigError::UnknownKey { key, position, .. }) => {
+                assert_eq!(key, "perod_seconds");
+                assert_eq!(position, Some(Position { line: 3, column: 1 }));
+            }
+            other => panic!("Unexpected result: {:?}", other.err()),
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn parse_error_has_position() {
+        let path = config_file("parse", "monitor = \"Connection\"\n[report]\nperiod_seconds = \"60\"\n");
+        let Err(error) = read_config(&path) else {
+            panic!("Config with a bad value was read");
+        };
+        assert!(matches!(
+            error,
+            ConfigError::Parse {
+                position: Some(Position { line: 3, column: 18 }),
+                ..
+            }
+        ));
+        assert!(error.to_string().contains("line 3, column 18"), "{error}");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn bad_url_and_zero_period_are_errors() {
+        let path = config_file("url", "[report]\nbase_url = \"collectr.example.com\"\n");
+        assert!(matches!(
+            read_config(&path),
+            Err(ConfigError::InvalidUrl {
+                key: "report.base_url",
+                ..
+            })
+        ));
+        std::fs::write(&path, "[report]\nperiod_seconds = 0\n").unwrap();
+        assert!(matches!(
+            read_config(&path),
+            Err(ConfigError::ZeroPeriod { .. })
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn tls_options_rustls_does_not_support_are_errors() {
+        let path = config_file(
+            "tls",
+            "[report.tls]\npinned_public_keys = [\"sha256//YhKJKSzoTt2b5FP18fvpHo7fJYqQCjAa3HWY3tvRMwE=\"]\n",
+        );
+        let Err(error) = read_config(&path) else {
+            panic!("Config with pinned public keys was read");
+        };
+        assert!(matches!(
+            error,
+            ConfigError::Unsupported {
+                setting: "report.tls.pinned_public_keys",
+                ..
+            }
+        ));
+        assert!(error.to_string().contains("is not supported"), "{error}");
+
+        std::fs::write(
+            &path,
+            "[report.tls]\nclient_cert = \"client.pem\"\nclient_key = \"client.key\"\nclient_key_password = \"secret\"\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            read_config(&path),
+            Err(ConfigError::Unsupported {
+                setting: "report.tls.client_key_password",
+                ..
+            })
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn ssid_file_with_networks() {
+        let path = config_file(
+            "ssid",
+            "ssid_name = \"Site\"\nssid_pass = \"password\"\n\
+            [[networks]]\nssid = \"Home\"\npassword = \"home-password\"\nsecurity = \"Wpa2\"\n\
+            [[networks]]\nssid = \"Cafe\"\nsecurity = \"Open\"\n",
+        );
+        let ssid = read_ssid(&path).unwrap();
+        assert_eq!(ssid.ssid_name, Some("Site".to_string()));
+        let networks = ssid.networks.unwrap();
+        assert_eq!(networks[0].security, Some(WifiSecurity::Wpa2));
+        assert_eq!(networks[1].ssid, "Cafe");
+        assert_eq!(networks[1].password, None);
+
+        std::fs::write(&path, "[[networks]]\nssid = \"Home\"\npassword = \"short\"\n").unwrap();
+        let Err(error) = read_ssid(&path) else {
+            panic!("Network with a short password was read");
+        };
+        assert!(matches!(error, ConfigError::InvalidNetwork { ref ssid, .. } if ssid == "Home"));
+
+        // picomon can't join WPA3 networks yet
+        std::fs::write(
+            &path,
+            "[[networks]]\nssid = \"Home\"\npassword = \"home-password\"\nsecurity = \"Wpa3\"\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            read_ssid(&path),
+            Err(ConfigError::InvalidNetwork { ref ssid, .. }) if ssid == "Home"
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn networks_are_not_read_from_the_config_file() {
+        let path = config_file("networks", "[[networks]]\nssid = \"Home\"\n");
+        assert!(matches!(
+            read_config(&path),
+            Err(ConfigError::UnknownKey { ref key, .. }) if key == "networks"
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+}
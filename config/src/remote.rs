@@ -0,0 +1,129 @@
+use std::path::PathBuf;
+
+use toml::Table;
+
+use crate::layers::{leaves, Settings};
+use crate::{Config, ConfigError, Source, SETTINGS};
+
+/// Settings that a remote config can't set, as they are specific to the host (e.g. paths of
+/// local files), choose the device's identity, or configure how the collector (and so the remote
+/// config itself) is reached, where a mistake would cut the device off from the collector
+pub const LOCAL_SETTINGS: &[&str] = &[
+    "data_dir",
+    "device.",
+    "remote.",
+    "report.base_url",
+    "report.no_proxy",
+    "report.proxy",
+    "report.tls.",
+    "service.",
+    "simulation.",
+];
+
+/// Settings for a device from the collector's config endpoint. They are in the same format as
+/// the config file, with the [Config] types as their schema, but only the [SETTINGS] that are
+/// not [LOCAL_SETTINGS] can be given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteSettings {
+    url: String,
+    settings: Settings,
+}
+
+impl RemoteSettings {
+    /// Parse and check the remote config `text`, fetched from `url`
+    pub fn parse(url: &str, text: &str) -> Result<Self, ConfigError> {
+        // Errors are reported against the URL, as they would be against a config file's path
+        let path = PathBuf::from(url);
+        toml::from_str::<Config>(text)
+            .map_err(|error| ConfigError::parse(path.clone(), text, error))?;
+        let table: Table =
+            toml::from_str(text).map_err(|error| ConfigError::parse(path, text, error))?;
+
+        let source = Source::Remote(url.to_string());
+        let mut settings = vec![];
+        leaves(&table, "", &mut settings);
+        for (setting, value) in &settings {
+            // An empty section, which enables a feature, is given as an empty table
+            let section = value.as_table().is_some_and(Table::is_empty)
+                && SETTINGS
+                    .iter()
+                    .any(|known| known.starts_with(&format!("{setting}.")));
+            if !SETTINGS.contains(&setting.as_str()) && !section {
+                return Err(ConfigError::UnknownSetting {
+                    setting: setting.clone(),
+                    source,
+                });
+            }
+            if is_local(setting) {
+                return Err(ConfigError::Override {
+                    setting: setting.clone(),
+                    source,
+                    message: "can only be set locally".into(),
+                });
+            }
+        }
+
+        Ok(RemoteSettings {
+            url: url.to_string(),
+            settings,
+        })
+    }
+
+    /// The URL the settings were fetched from
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub(crate) fn settings(&self) -> &Settings {
+        &self.settings
+    }
+}
+
+fn is_local(setting: &str) -> bool {
+    LOCAL_SETTINGS
+        .iter()
+        .any(|local| match local.strip_suffix('.') {
+            Some(section) => setting.starts_with(local) || setting == section,
+            None => setting == *local,
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::RemoteSettings;
+    use crate::ConfigError;
+
+    const URL: &str = "https://collectr.example.com/config?device_id=abc123";
+
+    #[test]
+    fn only_settings_that_are_not_local() {
+        let remote = RemoteSettings::parse(
+            URL,
+            "[report]\nperiod_seconds = 30\n[probe]\ntargets = [\"1.1.1.1:53\"]\n",
+        )
+        .unwrap();
+        assert_eq!(remote.settings().len(), 2);
+        assert!(RemoteSettings::parse(URL, "")
+            .unwrap()
+            .settings()
+            .is_empty());
+
+        assert!(matches!(
+            RemoteSettings::parse(URL, "[service]\nusername = \"root\"\n"),
+            Err(ConfigError::Override { setting, .. }) if setting == "service.username"
+        ));
+        assert!(matches!(
+            RemoteSettings::parse(URL, "[report]\nproxy = \"http://proxy:3128\"\n"),
+            Err(ConfigError::Override { setting, .. }) if setting == "report.proxy"
+        ));
+        // Hooks run commands, so can only be in the config file
+        assert!(matches!(
+            RemoteSettings::parse(URL, "[[hooks]]\nevent = \"SsidChanged\"\ncommand = \"sh\"\n"),
+            Err(ConfigError::UnknownSetting { setting, .. }) if setting == "hooks"
+        ));
+        assert!(matches!(
+            RemoteSettings::parse(URL, "[report]\nperod_seconds = 30\n"),
+            Err(ConfigError::UnknownKey { .. })
+        ));
+    }
+}
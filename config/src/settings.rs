@@ -0,0 +1,252 @@
+use std::cell::RefCell;
+use std::sync::LazyLock;
+
+use serde::de::value::{Error, MapDeserializer, SeqDeserializer, StrDeserializer};
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::{forward_to_deserialize_any, Deserialize, Deserializer};
+
+use crate::Config;
+
+/// The settings that can be overridden by environment variables and on the command line, by
+/// their dotted path in the config file. They are the fields of [Config] that are not sections
+/// of other settings, apart from lists of sections (e.g. `hooks`), which can only be given in
+/// the config file.
+pub static SETTINGS: LazyLock<Vec<&'static str>> = LazyLock::new(|| {
+    fields()
+        .into_iter()
+        .filter(|(_, setting)| *setting)
+        .map(|(path, _)| &*Box::leak(path.into_boxed_str()))
+        .collect()
+});
+
+/// Settings whose values are not shown by [crate::LayeredConfig::describe]: the passwords
+pub(crate) static SECRET_SETTINGS: LazyLock<Vec<&'static str>> = LazyLock::new(|| {
+    SETTINGS
+        .iter()
+        .copied()
+        .filter(|setting| setting.ends_with("password"))
+        .collect()
+});
+
+// The dotted path of each field of the config model that is not a section, and whether it is a
+// setting (rather than a list of sections). They are found by deserializing the model from
+// [Fields], and as a walk stops at the first new field it finds, the model is walked until all
+// of them have been found.
+fn fields() -> Vec<(String, bool)> {
+    let found = RefCell::new(vec![]);
+    loop {
+        let count = found.borrow().len();
+        let fields = Fields {
+            path: String::new(),
+            in_list: false,
+            found: &found,
+        };
+        match Config::deserialize(fields) {
+            Ok(_) => return found.into_inner(),
+            Err(e) if found.borrow().len() == count => {
+                panic!("Could not walk the fields of the config model: {e}")
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+// A field of the model, at `path`, or an element of the list at `path` if `in_list`
+struct Fields<'a> {
+    path: String,
+    in_list: bool,
+    found: &'a RefCell<Vec<(String, bool)>>,
+}
+
+impl Fields<'_> {
+    fn is_found(&self) -> bool {
+        self.found
+            .borrow()
+            .iter()
+            .any(|(path, _)| *path == self.path)
+    }
+
+    fn find(self, setting: bool) -> Error {
+        self.found.borrow_mut().push((self.path, setting));
+        de::Error::custom("found a field")
+    }
+}
+
+impl<'de> Deserializer<'de> for Fields<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(self.find(true))
+    }
+
+    // Fields that have been found are left out in later walks
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.is_found() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    // Except text and lists that must be given
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.is_found() {
+            visitor.visit_str("")
+        } else {
+            self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    // A list is walked with one element, to find if it is a list of sections
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.is_found() {
+            visitor.visit_seq(SeqDeserializer::<_, Error>::new(std::iter::empty::<()>()))
+        } else {
+            visitor.visit_seq(Element(Some(self)))
+        }
+    }
+
+    // As is a map, e.g. of named profiles
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.is_found() {
+            visitor.visit_map(MapDeserializer::<_, Error>::new(
+                std::iter::empty::<((), ())>(),
+            ))
+        } else {
+            visitor.visit_map(Element(Some(self)))
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if self.in_list {
+            return Err(self.find(false));
+        }
+        visitor.visit_map(Section {
+            fields: fields.iter(),
+            path: self.path,
+            field: String::new(),
+            found: self.found,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char bytes byte_buf unit
+        unit_struct newtype_struct tuple tuple_struct enum identifier ignored_any
+    }
+}
+
+// The fields of a section, each given with its path as the value
+struct Section<'a> {
+    fields: std::slice::Iter<'static, &'static str>,
+    path: String,
+    field: String,
+    found: &'a RefCell<Vec<(String, bool)>>,
+}
+
+impl<'de> MapAccess<'de> for Section<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let Some(field) = self.fields.next() else {
+            return Ok(None);
+        };
+        self.field = match self.path.as_str() {
+            "" => field.to_string(),
+            path => format!("{path}.{field}"),
+        };
+        let key: StrDeserializer<Error> = field.into_deserializer();
+        seed.deserialize(key).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(Fields {
+            path: std::mem::take(&mut self.field),
+            in_list: false,
+            found: self.found,
+        })
+    }
+}
+
+// The one element of a list or map, with the list's path
+struct Element<'a>(Option<Fields<'a>>);
+
+impl<'de> SeqAccess<'de> for Element<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        let Some(list) = self.0.take() else {
+            return Ok(None);
+        };
+        seed.deserialize(Fields {
+            in_list: true,
+            ..list
+        })
+        .map(Some)
+    }
+}
+
+impl<'de> MapAccess<'de> for Element<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if self.0.is_none() {
+            return Ok(None);
+        }
+        let key: StrDeserializer<Error> = "name".into_deserializer();
+        seed.deserialize(key).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        self.next_element_seed(seed)?
+            .ok_or_else(|| de::Error::custom("no value"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SECRET_SETTINGS, SETTINGS};
+
+    #[test]
+    fn settings_are_the_fields_of_the_model() {
+        for setting in [
+            "monitor",
+            "data_dir",
+            "report.tls.ca_files",
+            "service.environment",
+        ] {
+            assert!(SETTINGS.contains(&setting), "{setting}");
+        }
+        assert!(SETTINGS.contains(&"service.sandbox.read_write_paths"));
+
+        // Not sections, lists of sections or the values derived from the settings
+        for field in [
+            "report",
+            "hooks",
+            "profiles",
+            "overrides",
+            "networks",
+            "period_duration",
+        ] {
+            assert!(!SETTINGS.contains(&field), "{field}");
+        }
+        assert_eq!(*SECRET_SETTINGS, ["report.tls.client_key_password"]);
+    }
+}
@@ -8,9 +8,76 @@ pub type DeviceId = String;
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Stats {
     pub power_dbs: i16,
+    /// Statistics of the signal level (in dBm) sampled during the report period
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub samples: Option<SampleStats>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Statistics of a wireless link from the kernel. Counters are the change during the report
+/// period, and are not present for the first period after (re)connecting.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct LinkStats {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_bitrate_mbps: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rx_bitrate_mbps: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_mcs: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rx_mcs: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_retries: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_failed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub beacon_loss: Option<u64>,
+    /// How long the device has been connected to the access point
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connected_seconds: Option<u64>,
+}
+
+/// Statistics of the samples of a value taken during a report period. The values are only
+/// present if at least one sample succeeded.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SampleStats {
+    pub count: u32,
+    pub failed: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mean: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p50: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p95: Option<f32>,
+}
+
+impl SampleStats {
+    /// Calculate the statistics of some samples, where None is a failed sample
+    pub fn from_samples(samples: &[Option<f32>]) -> Self {
+        let mut values: Vec<f32> = samples.iter().flatten().copied().collect();
+        values.sort_by(f32::total_cmp);
+        let percentile = |p: usize| {
+            // nearest-rank method
+            let rank = (p * values.len()).div_ceil(100).max(1);
+            values.get(rank - 1).copied()
+        };
+
+        SampleStats {
+            count: samples.len() as u32,
+            failed: (samples.len() - values.len()) as u32,
+            min: values.first().copied(),
+            max: values.last().copied(),
+            mean: (!values.is_empty()).then(|| values.iter().sum::<f32>() / values.len() as f32),
+            p50: percentile(50),
+            p95: percentile(95),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Connection {
     SSID(String),
     Ethernet(String),
@@ -25,10 +92,112 @@ impl Display for Connection {
     }
 }
 
+/// The frequency band a Wi-Fi access point is using. 6GHz is not included, as its channel
+/// numbers overlap those of the other bands, and the scanner reports channels, not frequencies.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Band {
+    #[serde(rename = "2.4GHz")]
+    TwoPointFourGHz,
+    #[serde(rename = "5GHz")]
+    FiveGHz,
+}
+
+impl Display for Band {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Band::TwoPointFourGHz => write!(f, "2.4GHz"),
+            Band::FiveGHz => write!(f, "5GHz"),
+        }
+    }
+}
+
+/// One access point (BSSID) of a Wi-Fi network, as heard by the device
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AccessPoint {
+    pub bssid: String,
+    pub channel: Option<u16>,
+    pub band: Option<Band>,
+    pub security: Option<String>,
+    pub power_dbs: i16,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ConnectionReport {
     pub connection: Connection,
+    /// For a Wi-Fi network, the stats of the strongest access point heard
     pub stats: Option<Stats>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub access_points: Vec<AccessPoint>,
+    /// The network interface of the connection, when probes are run per interface
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    /// The results of the probes run bound to the connection's interface
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub probes: Vec<ProbeReport>,
+    /// For the Wi-Fi network used, statistics of the wireless link from the kernel
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<LinkStats>,
+}
+
+/// The result of probing a target, to check connectivity beyond the local link
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProbeReport {
+    pub target: String,
+    /// Time taken to connect, or None if the probe failed
+    pub latency_ms: Option<u32>,
+    /// Statistics of the latency (in ms) sampled during the report period
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub samples: Option<SampleStats>,
+}
+
+/// A hop on the path to a traced target
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Hop {
+    pub ttl: u8,
+    /// The address of the router that replied, or None if there was no reply
+    pub address: Option<String>,
+    pub rtt_ms: Option<f32>,
+}
+
+/// The network path to a target, as traced by the device
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PathReport {
+    pub target: String,
+    /// A hash of the addresses of the hops, which changes when the path changes
+    pub fingerprint: String,
+    /// The hops on the path, only included when the path has changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hops: Option<Vec<Hop>>,
+}
+
+/// The addressing of the device: its local addresses, gateway, DNS servers and public IP
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AddressingReport {
+    /// Local (global scope) addresses, with their prefix length, e.g. "192.168.1.23/24"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<String>,
+    pub gateway: Option<String>,
+    pub gateway_mac: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dns_servers: Vec<String>,
+    /// When the DHCP lease of the address expires, in seconds since the Unix EPOCH
+    pub dhcp_lease_expiry: Option<u64>,
+    /// The public IP, as seen by the echo endpoint
+    pub public_ip: Option<String>,
+    /// Which of the above changed since the previous report, e.g. "public_ip"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changed: Vec<String>,
+}
+
+/// A change of the network path from a device to a target, recorded by the collector
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PathChange {
+    pub id: String,
+    pub target: String,
+    pub previous_fingerprint: Option<String>,
+    pub fingerprint: String,
+    pub hops: Vec<Hop>,
+    pub timestamp: u64, // millis in Unix EPOCH
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -51,6 +220,12 @@ pub struct MonitorReport {
     pub connection_used: Connection,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub connections: Vec<ConnectionReport>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub probes: Vec<ProbeReport>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<PathReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub addressing: Option<AddressingReport>,
 }
 
 impl Default for MonitorReport {
@@ -58,6 +233,9 @@ impl Default for MonitorReport {
         MonitorReport {
             connection_used: Connection::Ethernet("default".to_string()),
             connections: vec![],
+            probes: vec![],
+            paths: vec![],
+            addressing: None,
         }
     }
 }
@@ -113,4 +291,32 @@ pub struct StateChange {
     pub state: DeviceState,
     pub connection: Option<String>,
     pub timestamp: u64, // millis in Unix EPOCH
+    /// The reason given by the device for the change, if any. e.g. "suspend"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::SampleStats;
+
+    #[test]
+    fn sample_stats() {
+        let samples: Vec<Option<f32>> = (1..=19)
+            .map(|i| Some(i as f32))
+            .chain([None, Some(20.0)])
+            .collect();
+        let stats = SampleStats::from_samples(&samples);
+        assert_eq!(stats.count, 21);
+        assert_eq!(stats.failed, 1);
+        assert_eq!(stats.min, Some(1.0));
+        assert_eq!(stats.max, Some(20.0));
+        assert_eq!(stats.mean, Some(10.5));
+        assert_eq!(stats.p50, Some(10.0));
+        assert_eq!(stats.p95, Some(19.0));
+
+        let failed = SampleStats::from_samples(&[None, None]);
+        assert_eq!(failed.failed, 2);
+        assert_eq!(failed.mean, None);
+    }
 }
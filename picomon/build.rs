@@ -15,27 +15,21 @@ use std::path::{Path, PathBuf};
 
 use config::SsidSpec;
 
-#[path = "src/pico_config.rs"]
-mod pico_config;
-
 const CONFIG_FILE_NAME: &str = "monitor.toml";
 const SSID_FILE_NAME: &str = "ssid.toml";
 const SSID_NAME_LENGTH: usize = 32;
 const SSID_PASS_LENGTH: usize = 63;
+// picomon keeps track of the networks seen in a scan in a u64, including the one in the markers
+const MAX_NETWORKS: usize = 63;
 
 // Given a Config, a filename and an optional override of SSID details,
-// generate that as a source file in OUT_DIR
-fn generate_config(config: config::Config, filename: &str, ssid: SsidSpec) {
+// generate that as a source file in OUT_DIR, with the Config as a const
+// of the no_std model in the config crate, including the networks to join
+fn generate_config(config: &mut config::Config, filename: &str, ssid: SsidSpec) {
     let out = env::var("OUT_DIR").unwrap();
     let out_dir = Path::new(&out);
     let out_file = out_dir.join(filename);
     let mut file = File::create(out_file).unwrap();
-    file.write_all(b"use crate::pico_config::Config;\n")
-        .unwrap();
-    file.write_all(b"use crate::pico_config::MonitorSpec;\n")
-        .unwrap();
-    file.write_all(b"use crate::pico_config::ReportSpec;\n")
-        .unwrap();
     file.write_all(b"pub(crate) const MARKER_LENGTH : usize = \"$SSID_NAME::\".len();\n")
         .unwrap();
     file.write_all(b"pub(crate) const SSID_NAME_LENGTH : usize = 32;\n")
@@ -48,7 +42,7 @@ fn generate_config(config: config::Config, filename: &str, ssid: SsidSpec) {
     file.write_all(
         format!(
             "pub(crate) const SSID_NAME : &str = \"$SSID_NAME::{: <SSID_NAME_LENGTH$}$SSID_NAME::\";\n",
-            ssid.ssid_name
+            ssid.ssid_name.unwrap_or_default()
         )
             .as_bytes(),
     )
@@ -57,62 +51,19 @@ fn generate_config(config: config::Config, filename: &str, ssid: SsidSpec) {
     file.write_all(
         format!(
             "pub(crate) const SSID_PASS : &str = \"$SSID_PASS::{: <SSID_PASS_LENGTH$}$SSID_PASS::\";\n",
-            ssid.ssid_pass
+            ssid.ssid_pass.unwrap_or_default()
         )
             .as_bytes(),
     )
         .unwrap();
 
-    file.write_all(b"pub(crate) const CONFIG: Config = ")
+    config.networks = ssid.networks;
+    let source = config::to_rust(config, "::config").unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    file.write_all(format!("pub(crate) const CONFIG: ::config::Config = {source};\n").as_bytes())
         .unwrap();
-
-    file.write_all(b"Config {").unwrap();
-    match &config.monitor {
-        Some(monitor) => {
-            file.write_all(b"    monitor: ").unwrap();
-            match monitor {
-                config::MonitorSpec::All => file.write(b"MonitorSpec::All").unwrap(),
-                config::MonitorSpec::Connection => file.write(b"MonitorSpec::Connection").unwrap(),
-            };
-            file.write_all(b"    ,").unwrap()
-        }
-        None => {
-            // TODO fail or substitute a default value here
-            file.write_all(b"    monitor: None,").unwrap()
-        }
-    };
-
-    match &config.report {
-        Some(report) => {
-            file.write_all(b"    report: ").unwrap();
-            file.write_all(b"ReportSpec {").unwrap();
-            file.write_all(
-                format!(
-                    "        period_seconds: {},",
-                    report.period_seconds.unwrap()
-                )
-                    .as_bytes(),
-            )
-                .unwrap();
-            file.write_all(
-                format!(
-                    "        base_url: \"{}\",",
-                    report.base_url.as_ref().unwrap()
-                )
-                    .as_bytes(),
-            )
-                .unwrap();
-            file.write_all(b"    }").unwrap();
-            file.write_all(b"    ,").unwrap()
-        }
-        None => {
-            // TODO fail or substitute a default value here
-            file.write_all(b"    report: None,").unwrap()
-        }
-    };
-    file.write_all(b"}").unwrap();
-
-    file.write_all(b";").unwrap();
 }
 
 fn main() -> io::Result<()> {
@@ -137,10 +88,29 @@ fn main() -> io::Result<()> {
     println!("cargo:rustc-link-arg-bins=-Tlink-rp.x");
     println!("cargo:rustc-link-arg-bins=-Tdefmt.x");
 
-    // Generate a pico_config::Config struct for picomon from the monitor.toml file
-    let config_file_path =
-        config::find_config_file(CONFIG_FILE_NAME)?;
-    let config = config::read_config(&config_file_path).unwrap();
+    // Settings can be overridden by environment variables, and the device tags and ID set by
+    // them choose which of the overrides in a fleet's monitor.toml apply to this build
+    for setting in config::SETTINGS.iter() {
+        println!("cargo:rerun-if-env-changed={}", config::env_name(setting));
+    }
+    println!("cargo:rerun-if-env-changed={}", config::CONFIG_FILE_ENV);
+
+    // Generate the config for picomon from the monitor.toml file
+    let config_file_path = match env::var(config::CONFIG_FILE_ENV) {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => config::find_config_file(CONFIG_FILE_NAME)?,
+    };
+    println!("cargo:rerun-if-changed={}", config_file_path.display());
+    let mut config = config::read_config(&config_file_path).unwrap_or_else(|e| {
+        // Cargo shows the output of a build script that fails
+        eprintln!("Invalid config file: {e}");
+        std::process::exit(1);
+    });
+    // picomon has no default collector to report to
+    if config.report_url.is_none() {
+        eprintln!("Invalid config file: picomon needs a 'base_url' in its [report] section");
+        std::process::exit(1);
+    }
     // rebuild if ../monitor.toml changes
     println!("cargo:rerun-if-changed=../monitor.toml");
 
@@ -148,10 +118,22 @@ fn main() -> io::Result<()> {
         .parent()
         .unwrap()
         .join(SSID_FILE_NAME);
-    // If we can't find a valid SsidSpec (e.g. when running in CI), then use a default one
-    let ssid_spec = config::read_ssid(&ssid_path).unwrap_or(SsidSpec::default());
+    // If there is no SsidSpec (e.g. when running in CI), then use a default one
+    let ssid_spec = match config::read_ssid(&ssid_path) {
+        Ok(ssid_spec) => ssid_spec,
+        // A missing file is expected, but a file with errors in it is not
+        Err(config::ConfigError::Read { .. }) => SsidSpec::default(),
+        Err(e) => {
+            eprintln!("Invalid SSID file: {e}");
+            std::process::exit(1);
+        }
+    };
+    if ssid_spec.networks.as_ref().is_some_and(|networks| networks.len() > MAX_NETWORKS) {
+        eprintln!("Invalid SSID file: picomon can have at most {MAX_NETWORKS} networks");
+        std::process::exit(1);
+    }
 
-    generate_config(config, "config.rs", ssid_spec);
+    generate_config(&mut config, "config.rs", ssid_spec);
 
     Ok(())
 }
@@ -16,32 +16,32 @@ use embassy_net::{
 };
 use embassy_net::dns::DnsSocket;
 use embassy_rp::bind_interrupts;
+use embassy_rp::clocks::RoscRng;
 use embassy_rp::flash::Async;
 use embassy_rp::gpio::{Level, Output};
 use embassy_rp::peripherals::{DMA_CH0, PIO0};
 use embassy_rp::peripherals::USB;
 use embassy_rp::pio::{InterruptHandler, Pio};
 use embassy_rp::usb::{Driver, InterruptHandler as USBInterruptHandler};
-use embassy_time::{Duration, Timer};
+use embassy_time::{Duration, Instant, Timer};
 use faster_hex::hex_encode;
 use log::{error, info};
 use panic_probe as _;
+use rand_core::RngCore;
 use reqwless::{client::HttpClient, request::Method};
 use reqwless::{client::TlsConfig, client::TlsVerify};
 use static_cell::StaticCell;
 
-use config::CONFIG;
-use pico_config::Config;
-use report_url::ReportUrl;
+use config::{phase_offset, Config, NetworkSpec, WifiSecurity};
+use generated::CONFIG;
+use report_url::{Encoded, ReportUrl};
 
-use crate::config::{MARKER_LENGTH, SSID_NAME, SSID_NAME_LENGTH, SSID_PASS, SSID_PASS_LENGTH};
-
-mod pico_config;
+use crate::generated::{MARKER_LENGTH, SSID_NAME, SSID_NAME_LENGTH, SSID_PASS, SSID_PASS_LENGTH};
 
 mod report_url;
 
 // The config generated by build.rs in OUT_DIR when compiling
-mod config {
+mod generated {
     include!(concat!(env!("OUT_DIR"), "/config.rs"));
 }
 
@@ -49,6 +49,9 @@ const FLASH_SIZE: usize = 2 * 1024 * 1024;
 
 const WIFI_JOIN_RETRY_ATTEMPT_LIMIT: usize = 3;
 
+// The networks seen in a scan are kept in a u64 mask, by their index in known_networks()
+const MAX_NETWORKS: usize = u64::BITS as usize;
+
 bind_interrupts!(struct Irqs {
     PIO0_IRQ_0 => InterruptHandler<PIO0>;
     USBCTRL_IRQ => USBInterruptHandler<USB>;
@@ -71,16 +74,61 @@ async fn logger_task(driver: Driver<'static, USB>) {
     embassy_usb_logger::run!(1024, log::LevelFilter::Info, driver);
 }
 
-/*
-async fn list_ssid(control: &mut Control<'_>) {
-    let mut scanner = control.scan().await;
-    let bss = scanner.next().await.unwrap();
-    match str::from_utf8(&bss.ssid) {
-        Ok(ssid_str) => info!("{}", ssid_str), /* info!("scanned {} == {:x}", ssid_str, bss.bssid),*/
-        Err(_) => log::info!("Could not scan wifi"),
+// The network in the SSID markers (which configr can change in the binary), if set, then the
+// networks in the config, in order of preference
+fn known_networks() -> impl Iterator<Item = NetworkSpec> {
+    let ssid = SSID_NAME[MARKER_LENGTH..(MARKER_LENGTH + SSID_NAME_LENGTH)].trim();
+    let pass = SSID_PASS[MARKER_LENGTH..(MARKER_LENGTH + SSID_PASS_LENGTH)].trim();
+    let marked = NetworkSpec {
+        ssid,
+        password: Some(pass).filter(|pass| !pass.is_empty()),
+        security: Some(match pass {
+            "" => WifiSecurity::Open,
+            _ => WifiSecurity::Wpa2,
+        }),
+    };
+    Some(marked)
+        .filter(|network| !network.ssid.is_empty())
+        .into_iter()
+        .chain(CONFIG.networks.unwrap_or_default().iter().cloned())
+        .take(MAX_NETWORKS)
+}
+
+// Scan for wifi networks, returning a mask of the known networks that were seen
+async fn scan_known_networks(control: &mut Control<'_>) -> u64 {
+    let mut seen = 0u64;
+    let mut scanner = control.scan(Default::default()).await;
+    while let Some(bss) = scanner.next().await {
+        let Ok(ssid) = core::str::from_utf8(&bss.ssid[..bss.ssid_len as usize]) else {
+            continue;
+        };
+        if let Some(index) = known_networks().position(|network| network.ssid == ssid) {
+            if seen & (1 << index) == 0 {
+                info!("Found known wifi network: '{}' ({}dBm)", ssid, bss.rssi);
+            }
+            seen |= 1 << index;
+        }
+    }
+    seen
+}
+
+// Join a network using its security, returning true if it was joined
+async fn join(control: &mut Control<'_>, network: &NetworkSpec) -> bool {
+    let password = network.password.unwrap_or_default();
+    let result = match network.security.unwrap_or_default() {
+        WifiSecurity::Wpa2 => control.join_wpa2(network.ssid, password).await,
+        // Rejected by build.rs, as the cyw43 firmware can't join WPA3 networks yet
+        WifiSecurity::Wpa3 => {
+            error!("Can't join WPA3 wifi network '{}'", network.ssid);
+            return false;
+        }
+        WifiSecurity::Open => control.join_open(network.ssid).await,
+    };
+    if let Err(e) = &result {
+        error!("Error joining wifi network '{}': {:?}", network.ssid, e);
     }
+    result.is_ok()
 }
-*/
 
 async fn wait_for_dhcp(stack: &Stack<NetDriver<'static>>) {
     info!("Waiting for DHCP...");
@@ -95,13 +143,19 @@ async fn monitor_loop<'a>(
     ssid: &str,
     stack: &Stack<NetDriver<'static>>,
     control: &mut Control<'_>,
-    config: Config,
+    config: &Config,
 ) {
     let mut report_count = 0;
-    let period_seconds = config.report.period_seconds;
+    let report = config.report.as_ref();
+    let period_seconds = report
+        .and_then(|report| report.period_seconds)
+        .unwrap_or(60);
     let report_delay = Duration::from_secs(period_seconds);
 
-    let base_url = config.report.base_url;
+    // build.rs checks that there is a base_url
+    let base_url = report
+        .and_then(|report| report.base_url)
+        .unwrap_or_default();
 
     let mut report_url = ReportUrl::new();
     write!(
@@ -109,7 +163,7 @@ async fn monitor_loop<'a>(
         "{}/report/ongoing?device_id={}&connection=ssid%3D{}&period={}",
         base_url,
         core::str::from_utf8(device_id_hex).unwrap(),
-        ssid,
+        Encoded(ssid),
         period_seconds
     )
         .unwrap();
@@ -129,6 +183,26 @@ async fn monitor_loop<'a>(
 
     let mut rx_buf = [0; 4096];
 
+    // Delay the first report by the device's phase offset and a random startup delay, so that
+    // devices that start together don't all report at the same time
+    let mut startup_delay = 0;
+    if report.and_then(|report| report.phase_offset).unwrap_or(false) {
+        let period = core::time::Duration::from_secs(period_seconds);
+        startup_delay += phase_offset(device_id_hex, period).as_secs();
+    }
+    let startup_jitter_seconds = report
+        .and_then(|report| report.startup_jitter_seconds)
+        .unwrap_or(0);
+    if startup_jitter_seconds > 0 {
+        startup_delay += RoscRng.next_u64() % startup_jitter_seconds;
+    }
+    info!("First report in {startup_delay}s");
+    Timer::after_secs(startup_delay).await;
+
+    // Reports are due at deadlines a period apart, so the time taken to send them doesn't
+    // make the schedule drift
+    let mut next_report = Instant::now();
+
     info!("Starting monitoring loop - will report every {period_seconds}s");
     loop {
         info!("Sending report #{}", report_count);
@@ -156,8 +230,18 @@ async fn monitor_loop<'a>(
 
         report_count += 1;
 
+        next_report += report_delay;
+        let mut missed = 0;
+        while next_report <= Instant::now() {
+            next_report += report_delay;
+            missed += 1;
+        }
+        if missed > 0 {
+            error!("Missed {} scheduled report(s)", missed);
+        }
+
         info!("Waiting");
-        Timer::after(report_delay).await;
+        Timer::at(next_report).await;
     }
 }
 
@@ -218,24 +302,28 @@ async fn main(spawner: Spawner) {
 
     spawner.spawn(net_task(stack)).unwrap();
 
-    let ssid_name = SSID_NAME[MARKER_LENGTH..(MARKER_LENGTH + SSID_NAME_LENGTH)].trim();
-    let ssid_pass = SSID_PASS[MARKER_LENGTH..(MARKER_LENGTH + SSID_PASS_LENGTH)].trim();
-
+    // Join the most preferred of the known networks that are seen in a scan, falling back to
+    // the less preferred ones if it can't be joined
     let mut attempt = 1;
     while attempt <= WIFI_JOIN_RETRY_ATTEMPT_LIMIT {
-        info!("Attempt #{} to join wifi network: '{}'", attempt, ssid_name);
-        match control.join_wpa2(ssid_name, ssid_pass).await {
-            Ok(_) => {
-                info!("Joined wifi network: '{}'", ssid_name);
+        info!("Attempt #{} to join a known wifi network", attempt);
+        let seen = match scan_known_networks(&mut control).await {
+            // Hidden networks are not seen in a scan, so try all of them
+            0 => u64::MAX,
+            seen => seen,
+        };
+        for (_, network) in known_networks()
+            .enumerate()
+            .filter(|(index, _)| seen & (1 << index) != 0)
+        {
+            if join(&mut control, &network).await {
+                info!("Joined wifi network: '{}'", network.ssid);
                 wait_for_dhcp(stack).await;
                 control.gpio_set(0, false).await;
-                monitor_loop(&device_id_hex, ssid_name, stack, &mut control, CONFIG).await;
-            }
-            Err(e) => {
-                attempt += 1;
-                error!("Error joining wifi: {:?}", e);
+                monitor_loop(&device_id_hex, network.ssid, stack, &mut control, &CONFIG).await;
             }
         }
+        attempt += 1;
     }
     error!("Retry count exceeded");
     info!("Exiting");
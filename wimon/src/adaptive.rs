@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use config::AdaptiveSpec;
+use data_model::MonitorReport;
+
+/// [AdaptivePeriod] shortens the report period (down to a minimum) each time the connection is
+/// degraded, and relaxes it gradually (up to a maximum) while it is healthy
+pub(crate) struct AdaptivePeriod {
+    min: Duration,
+    max: Duration,
+    min_signal_dbm: Option<i16>,
+    current: Duration,
+    send_failed: bool,
+}
+
+impl AdaptivePeriod {
+    pub fn new(spec: &AdaptiveSpec, period: Duration) -> Self {
+        let min = spec
+            .min_period_seconds
+            .map(Duration::from_secs)
+            .unwrap_or(period / 4);
+        let max = spec
+            .max_period_seconds
+            .map(Duration::from_secs)
+            .unwrap_or(period * 4)
+            .max(min);
+        AdaptivePeriod {
+            min,
+            max,
+            min_signal_dbm: spec.min_signal_dbm,
+            current: period.clamp(min, max),
+            send_failed: false,
+        }
+    }
+
+    /// Is the connection degraded, according to a report: a probe failed, or the signal of
+    /// the connection used is below the threshold, including in any samples taken during the period
+    pub fn degraded(&self, report: &MonitorReport) -> bool {
+        let probe_failed = report.probes.iter().any(|probe| {
+            probe.latency_ms.is_none() || probe.samples.as_ref().is_some_and(|s| s.failed > 0)
+        });
+        let weak_signal = match self.min_signal_dbm {
+            Some(min_signal_dbm) => report
+                .connections
+                .iter()
+                .filter(|c| c.connection.to_string() == report.connection_used.to_string())
+                .filter_map(|c| c.stats.as_ref())
+                .any(|stats| {
+                    let min = stats.samples.as_ref().and_then(|samples| samples.min);
+                    min.unwrap_or(stats.power_dbs as f32) < min_signal_dbm as f32
+                }),
+            None => false,
+        };
+        probe_failed || weak_signal
+    }
+
+    /// Failing to send a report is also a sign of a degraded connection. The period has already
+    /// been sent with the report, so the failure counts towards the next update.
+    pub fn send_failed(&mut self) {
+        self.send_failed = true;
+    }
+
+    /// Calculate the next period, halving it if degraded (or the last send failed) and
+    /// increasing it by half if healthy
+    pub fn update(&mut self, degraded: bool) -> Duration {
+        self.current = if degraded || std::mem::take(&mut self.send_failed) {
+            (self.current / 2).max(self.min)
+        } else {
+            (self.current + self.current / 2).min(self.max)
+        };
+        self.current
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use config::AdaptiveSpec;
+
+    use super::AdaptivePeriod;
+
+    #[test]
+    fn shorten_and_relax() {
+        let spec = AdaptiveSpec {
+            min_period_seconds: Some(10),
+            max_period_seconds: Some(90),
+            min_signal_dbm: None,
+        };
+        let mut adaptive = AdaptivePeriod::new(&spec, Duration::from_secs(60));
+        assert_eq!(adaptive.update(true), Duration::from_secs(30));
+        assert_eq!(adaptive.update(true), Duration::from_secs(15));
+        assert_eq!(adaptive.update(true), Duration::from_secs(10));
+        assert_eq!(adaptive.update(false), Duration::from_secs(15));
+        assert_eq!(adaptive.update(false), Duration::from_millis(22500));
+        for _ in 0..5 {
+            adaptive.update(false);
+        }
+        assert_eq!(adaptive.update(false), Duration::from_secs(90));
+
+        // A failed send shortens the next period, once
+        adaptive.send_failed();
+        assert_eq!(adaptive.update(false), Duration::from_secs(45));
+        assert_eq!(adaptive.update(false), Duration::from_millis(67500));
+    }
+}
@@ -0,0 +1,309 @@
+use std::fs;
+use std::io;
+use std::process::Command;
+use std::time::Duration;
+
+use config::Config;
+use curl::easy::Easy;
+use data_model::{AddressingReport, MonitorReport};
+
+use crate::monitor::Source;
+#[cfg(target_os = "linux")]
+use crate::state::now;
+use crate::transport;
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+const PUBLIC_IP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// [AddressingSource] reports the local addresses, gateway (and its MAC), DNS servers, DHCP
+/// lease expiry and public IP of the device, and which of them changed since the last report.
+/// A part that can't be measured (e.g. the public IP endpoint is unreachable) keeps its previous
+/// value, so that a transient failure isn't reported as a change, and then another back.
+pub struct AddressingSource {
+    config: Config,
+    public_ip_url: Option<String>,
+    previous: Option<AddressingReport>,
+}
+
+impl AddressingSource {
+    /// Create an [AddressingSource] if addressing is configured and enabled
+    pub fn new(config: &Config) -> Option<Self> {
+        let spec = config
+            .addressing
+            .as_ref()
+            .filter(|spec| spec.enabled.unwrap_or(true))?;
+        Some(AddressingSource {
+            config: config.clone(),
+            public_ip_url: spec.public_ip_url.clone(),
+            previous: None,
+        })
+    }
+}
+
+impl Source for AddressingSource {
+    fn measure(&mut self, report: &mut MonitorReport) -> Result<(), io::Error> {
+        let mut addressing = get_addressing(self.previous.as_ref());
+        if let Some(url) = &self.public_ip_url {
+            match get_public_ip(url, &self.config) {
+                Ok(public_ip) => addressing.public_ip = Some(public_ip),
+                Err(e) => eprintln!("Could not get public IP from '{url}': {e}"),
+            }
+        }
+        if let Some(previous) = &self.previous {
+            addressing.changed = changes(previous, &addressing);
+        }
+        self.previous = Some(addressing.clone());
+        report.addressing = Some(addressing);
+        Ok(())
+    }
+}
+
+// The names of the fields that changed between two measurements of the addressing
+fn changes(previous: &AddressingReport, current: &AddressingReport) -> Vec<String> {
+    let mut changed = vec![];
+    if previous.addresses != current.addresses {
+        changed.push("addresses".to_string());
+    }
+    if previous.gateway != current.gateway {
+        changed.push("gateway".to_string());
+    }
+    if previous.gateway_mac != current.gateway_mac {
+        changed.push("gateway_mac".to_string());
+    }
+    if previous.dns_servers != current.dns_servers {
+        changed.push("dns_servers".to_string());
+    }
+    // The expiry moves forward each time the lease is renewed, so only report it going missing
+    if previous.dhcp_lease_expiry.is_some() && current.dhcp_lease_expiry.is_none() {
+        changed.push("dhcp_lease".to_string());
+    }
+    if previous.public_ip != current.public_ip {
+        changed.push("public_ip".to_string());
+    }
+    changed
+}
+
+// Get the addressing from the OS. Each part is optional, so failures are logged and the part
+// keeps its value from the `previous` measurement, if there is one.
+fn get_addressing(previous: Option<&AddressingReport>) -> AddressingReport {
+    let mut addressing = AddressingReport {
+        changed: vec![],
+        ..previous.cloned().unwrap_or_default()
+    };
+    match get_addresses() {
+        Ok((addresses, lease_expiry)) => {
+            addressing.addresses = addresses;
+            addressing.dhcp_lease_expiry = lease_expiry;
+        }
+        Err(e) => eprintln!("Could not get local addresses: {e}"),
+    }
+    match get_gateway() {
+        Ok(gateway) => {
+            match get_mac(&gateway) {
+                Ok(mac) => addressing.gateway_mac = Some(mac),
+                // The previous MAC is only known if it's the same gateway
+                Err(_) if addressing.gateway.as_ref() != Some(&gateway) => {
+                    addressing.gateway_mac = None
+                }
+                Err(_) => {}
+            }
+            addressing.gateway = Some(gateway);
+        }
+        Err(e) => eprintln!("Could not get gateway: {e}"),
+    }
+    match fs::read_to_string(RESOLV_CONF) {
+        Ok(resolv_conf) => addressing.dns_servers = parse_resolv_conf(&resolv_conf),
+        Err(e) => eprintln!("Could not read '{RESOLV_CONF}': {e}"),
+    }
+    addressing
+}
+
+// Run a command, returning its output
+fn run(program: &str, args: &[&str]) -> Result<String, io::Error> {
+    let output = Command::new(program).args(args).output().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Could not execute '{program}'"),
+        )
+    })?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Get the global addresses, and the expiry of the first one obtained using DHCP, if any
+#[cfg(target_os = "linux")]
+fn get_addresses() -> Result<(Vec<String>, Option<u64>), io::Error> {
+    let (addresses, lease_seconds) =
+        parse_ip_addr(&run("ip", &["-o", "addr", "show", "scope", "global"])?);
+    Ok((addresses, lease_seconds.map(|seconds| now() + seconds)))
+}
+
+// Parse the output of `ip -o addr show scope global`, which has one line per address, e.g.
+// "2: wlan0    inet 192.168.1.23/24 brd 192.168.1.255 scope global dynamic wlan0\       valid_lft 85994sec ..."
+// Addresses obtained using DHCP are "dynamic", and valid for the remaining time of the lease
+#[cfg(target_os = "linux")]
+fn parse_ip_addr(data: &str) -> (Vec<String>, Option<u64>) {
+    let mut addresses = vec![];
+    let mut lease_seconds = None;
+    for line in data.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let after = |label: &str| {
+            words
+                .iter()
+                .position(|word| *word == label)
+                .and_then(|position| words.get(position + 1))
+        };
+        if let Some(address) = after("inet").or_else(|| after("inet6")) {
+            addresses.push(address.to_string());
+        }
+        if lease_seconds.is_none() && words.contains(&"dynamic") {
+            lease_seconds = after("valid_lft")
+                .and_then(|valid| valid.strip_suffix("sec"))
+                .and_then(|seconds| seconds.parse().ok());
+        }
+    }
+    (addresses, lease_seconds)
+}
+
+// Get the global addresses, from the "inet" lines of `ifconfig`. The DHCP lease expiry is
+// not available.
+#[cfg(target_os = "macos")]
+fn get_addresses() -> Result<(Vec<String>, Option<u64>), io::Error> {
+    let data = run("/sbin/ifconfig", &[])?;
+    let addresses = data
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("inet "))
+        .filter_map(|rest| rest.split_whitespace().next())
+        .filter(|address| !address.starts_with("127."))
+        .map(|address| address.to_string())
+        .collect();
+    Ok((addresses, None))
+}
+
+#[cfg(target_os = "linux")]
+fn get_gateway() -> Result<String, io::Error> {
+    parse_after(&run("ip", &["route", "show", "default"])?, "via")
+}
+
+#[cfg(target_os = "macos")]
+fn get_gateway() -> Result<String, io::Error> {
+    parse_after(&run("/sbin/route", &["-n", "get", "default"])?, "gateway:")
+}
+
+// Get the MAC address of a neighbour, e.g. "192.168.1.1 dev wlan0 lladdr aa:bb:cc:dd:ee:ff REACHABLE"
+#[cfg(target_os = "linux")]
+fn get_mac(address: &str) -> Result<String, io::Error> {
+    parse_after(&run("ip", &["neigh", "show", address])?, "lladdr")
+}
+
+// Get the MAC address of a neighbour, e.g. "? (192.168.1.1) at aa:bb:cc:dd:ee:ff on en0 ..."
+#[cfg(target_os = "macos")]
+fn get_mac(address: &str) -> Result<String, io::Error> {
+    parse_after(&run("/usr/sbin/arp", &["-n", address])?, "at")
+}
+
+// Parse the word following `label` in some command output
+fn parse_after(data: &str, label: &str) -> Result<String, io::Error> {
+    let mut words = data.split_whitespace();
+    words
+        .position(|word| word == label)
+        .and_then(|_| words.next())
+        .map(|word| word.to_string())
+        .ok_or(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Could not find '{label}' in '{}'", data.trim()),
+        ))
+}
+
+fn parse_resolv_conf(data: &str) -> Vec<String> {
+    data.lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .map(|server| server.trim().to_string())
+        .collect()
+}
+
+// Get the public IP from an endpoint that responds with the IP of the requester as plain text,
+// using the proxy and TLS options in the config, as reports do
+fn get_public_ip(url: &str, config: &Config) -> Result<String, io::Error> {
+    let mut data = Vec::new();
+    let mut easy = Easy::new();
+    easy.url(url).map_err(|_| {
+        io::Error::new(io::ErrorKind::NotFound, "Could not set url on curl request")
+    })?;
+    easy.timeout(PUBLIC_IP_TIMEOUT)?;
+    transport::configure(&mut easy, config)?;
+    {
+        let mut transfer = easy.transfer();
+        transfer.write_function(|new_data| {
+            data.extend_from_slice(new_data);
+            Ok(new_data.len())
+        })?;
+        transfer
+            .perform()
+            .map_err(|e| transport::request_error(&e))?;
+    }
+
+    let ip = String::from_utf8_lossy(&data).trim().to_string();
+    match ip.parse::<std::net::IpAddr>() {
+        Ok(_) => Ok(ip),
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Response is not an IP address: '{ip}'"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use data_model::AddressingReport;
+
+    use super::{changes, parse_after, parse_resolv_conf};
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parse_ip_addr() {
+        let data = "2: wlan0    inet 192.168.1.23/24 brd 192.168.1.255 scope global dynamic noprefixroute wlan0\\       valid_lft 85994sec preferred_lft 85994sec\n\
+        3: eth0    inet 10.0.0.2/8 scope global eth0\\       valid_lft forever preferred_lft forever\n";
+        let (addresses, lease_seconds) = super::parse_ip_addr(data);
+        assert_eq!(addresses, ["192.168.1.23/24", "10.0.0.2/8"]);
+        assert_eq!(lease_seconds, Some(85994));
+    }
+
+    #[test]
+    fn parse_addressing() {
+        assert_eq!(
+            parse_after(
+                "default via 192.168.1.1 dev wlan0 proto dhcp metric 600",
+                "via"
+            )
+            .unwrap(),
+            "192.168.1.1"
+        );
+        assert!(parse_after("", "via").is_err());
+        assert_eq!(
+            parse_resolv_conf("# Generated\nnameserver 1.1.1.1\nnameserver 8.8.8.8\nsearch lan\n"),
+            ["1.1.1.1", "8.8.8.8"]
+        );
+    }
+
+    #[test]
+    fn addressing_changes() {
+        let previous = AddressingReport {
+            gateway: Some("192.168.1.1".into()),
+            dhcp_lease_expiry: Some(1000),
+            public_ip: Some("203.0.113.7".into()),
+            ..Default::default()
+        };
+        let renewed = AddressingReport {
+            dhcp_lease_expiry: Some(2000),
+            ..previous.clone()
+        };
+        assert!(changes(&previous, &renewed).is_empty());
+
+        let current = AddressingReport {
+            public_ip: Some("203.0.113.8".into()),
+            dhcp_lease_expiry: None,
+            ..previous.clone()
+        };
+        assert_eq!(changes(&previous, &current), ["dhcp_lease", "public_ip"]);
+    }
+}
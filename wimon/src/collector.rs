@@ -0,0 +1,124 @@
+use std::io;
+use std::io::Read;
+
+use config::Config;
+use curl::easy::Easy;
+use serde_json::json;
+
+use crate::monitor::{Report, Sink};
+use crate::transport;
+
+/// [CollectorSink] sends reports to the collector at the `base_url` in the config, or prints them
+/// if there is none
+pub struct CollectorSink {
+    config: Config,
+}
+
+impl CollectorSink {
+    pub fn new(config: &Config) -> Self {
+        CollectorSink {
+            config: config.clone(),
+        }
+    }
+}
+
+impl Sink for CollectorSink {
+    fn send(&mut self, report: &Report) -> Result<(), io::Error> {
+        send_report(&self.config, report)
+    }
+}
+
+// Send a report to the collector, using the proxy and TLS options in the config
+fn send_report(config: &Config, sent: &Report) -> Result<(), io::Error> {
+    let (report_type, report) = (sent.report_type, sent.report);
+    // Parameters are encoded, as SSIDs can contain any characters, including '&'
+    let report_url = config.report_url.as_ref().map(|p| {
+        let mut url = p
+            .join(&format!(
+                "report/{}",
+                report_type.to_string().to_ascii_lowercase()
+            ))
+            .unwrap();
+        url.query_pairs_mut()
+            .append_pair("device_id", sent.device_id)
+            .append_pair("connection", report.connection_used.to_string().trim())
+            .append_pair("period", &sent.period.as_secs().to_string());
+        if let Some(reason) = sent.reason {
+            url.query_pairs_mut().append_pair("reason", reason);
+        }
+        url
+    });
+
+    let mut data = Vec::new();
+    if let Some(url) = &report_url {
+        let mut easy = Easy::new();
+        let json_string = format!(
+            "report={}",
+            easy.url_encode(json!(report).to_string().as_bytes())
+        );
+        let mut post_data = json_string.as_bytes();
+        let result;
+        easy.url(url.as_str()).map_err(|_| {
+            io::Error::new(io::ErrorKind::NotFound, "Could not set url on curl request")
+        })?;
+        transport::configure(&mut easy, config)?;
+        {
+            easy.post(true).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "Could not set POST on curl request",
+                )
+            })?;
+            easy.post_fields_copy(post_data).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "Could not add POST data on curl request",
+                )
+            })?;
+            easy.post_field_size(post_data.len() as u64).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "Could not set POST field size on curl request",
+                )
+            })?;
+            let mut transfer = easy.transfer();
+            transfer
+                .read_function(|buf| Ok(post_data.read(buf).unwrap()))
+                .map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "Could not read data for curl request",
+                    )
+                })?;
+            transfer.write_function(|new_data| {
+                data.extend_from_slice(new_data);
+                Ok(new_data.len())
+            })?;
+            result = transfer.perform();
+        }
+        // The collector responds with an error status if it could not accept the report
+        let result = result
+            .map_err(|e| transport::request_error(&e))
+            .and_then(|_| match easy.response_code().unwrap_or(0) {
+                code @ 400.. => Err(io::Error::other(format!(
+                    "Collector responded with HTTP status {code}: {}",
+                    String::from_utf8_lossy(&data).trim()
+                ))),
+                _ => Ok(()),
+            });
+        match result {
+            Ok(_) => {
+                println!("Sent {} report to: {}", report_type, url.host().unwrap());
+                println!("Response: {}", String::from_utf8_lossy(&data));
+            }
+            Err(ref e) => eprintln!(
+                "Error reporting to '{}': skipping report: {e}",
+                url.as_str(),
+            ),
+        }
+        result
+    } else {
+        println!("Local Status: \n{report}");
+        Ok(())
+    }
+}
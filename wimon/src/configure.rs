@@ -0,0 +1,406 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use config::{Config, ConfigError, Identity, LayeredConfig, NetworkSpec, SsidSpec, WifiSecurity};
+use data_model::ReportType;
+use wimon::Monitor;
+
+const SSID_FILE_NAME: &str = "ssid.toml";
+
+/// The reason sent with the test report, so that the collector can tell it from the periodic ones
+const CHECK_REASON: &str = "check";
+
+// Asks questions, and reads the answers, on the terminal
+struct Prompt<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    // Ask a question until the answer (or the default, if the answer is empty) can be parsed
+    fn ask<T>(
+        &mut self,
+        question: &str,
+        default: &str,
+        parse: impl Fn(&str) -> Result<T, String>,
+    ) -> Result<T, io::Error> {
+        loop {
+            match default {
+                "" => write!(self.output, "{question}: ")?,
+                default => write!(self.output, "{question} [{default}]: ")?,
+            }
+            self.output.flush()?;
+            let mut answer = String::new();
+            if self.input.read_line(&mut answer)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "No answer to the question",
+                ));
+            }
+            let answer = match answer.trim() {
+                "" => default,
+                answer => answer,
+            };
+            match parse(answer) {
+                Ok(value) => return Ok(value),
+                Err(e) => writeln!(self.output, "Not valid: {e}")?,
+            }
+        }
+    }
+
+    fn confirm(&mut self, question: &str) -> Result<bool, io::Error> {
+        self.ask(&format!("{question} (y/n)"), "n", |answer| {
+            match answer.to_ascii_lowercase().as_str() {
+                "y" | "yes" => Ok(true),
+                "n" | "no" => Ok(false),
+                _ => Err("answer y or n".to_string()),
+            }
+        })
+    }
+}
+
+/// Ask for the main settings, and write a config file with them, commented. Then optionally
+/// ask for Wi-Fi networks for picomon, and write them to the SSID file in the same directory.
+pub fn init_config(path: &Path, input: impl BufRead, output: impl Write) -> Result<(), io::Error> {
+    let mut prompt = Prompt { input, output };
+    if !can_write(&mut prompt, path)? {
+        return Ok(());
+    }
+
+    // Each answer is checked as a setting given with `--set` would be
+    let mut settings = vec![];
+    let base_url = prompt.ask(
+        "Collector base URL, e.g. https://collectr.example.com (none to print reports)",
+        "",
+        |url| match url {
+            "" => Ok(None),
+            url => check_setting("report.base_url", url).map(Some),
+        },
+    )?;
+    settings.extend(base_url);
+    settings.push(prompt.ask("Seconds between reports", "60", |period| {
+        check_setting("report.period_seconds", period)
+    })?);
+    settings.push(prompt.ask(
+        "Report on the Connection used, or on All the SSIDs seen",
+        "Connection",
+        |monitor| check_setting("monitor", monitor),
+    )?);
+    let targets = prompt.ask(
+        "Targets to probe, as host:port separated by commas (none to not probe)",
+        "",
+        parse_targets,
+    )?;
+    if !targets.is_empty() {
+        settings.push(format!("probe.targets={}", toml_value(&targets)));
+    }
+
+    let config = config::load_config_as(None, &settings, Identity::of, None)?.config;
+    fs::write(path, config_text(&config))?;
+    writeln!(prompt.output, "Wrote '{}'", path.display())?;
+
+    if prompt.confirm("Add Wi-Fi networks for picomon")? {
+        let ssid_path = path.with_file_name(SSID_FILE_NAME);
+        if can_write(&mut prompt, &ssid_path)? {
+            let networks = ask_networks(&mut prompt)?;
+            fs::write(&ssid_path, ssid_text(networks)?)?;
+            writeln!(prompt.output, "Wrote '{}'", ssid_path.display())?;
+        }
+    }
+
+    writeln!(
+        prompt.output,
+        "Check the config, and sending reports, with: wimon config check {}",
+        path.display()
+    )
+}
+
+// Check that a file can be written, asking before replacing one
+fn can_write(prompt: &mut Prompt<impl BufRead, impl Write>, path: &Path) -> io::Result<bool> {
+    if path.exists() && !prompt.confirm(&format!("Replace '{}'", path.display()))? {
+        writeln!(prompt.output, "Not writing '{}'", path.display())?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+// Ask for networks, in order of preference, until no SSID is given
+fn ask_networks(
+    prompt: &mut Prompt<impl BufRead, impl Write>,
+) -> Result<Vec<NetworkSpec>, io::Error> {
+    let mut networks = vec![];
+    loop {
+        let question = match networks.len() {
+            0 => "SSID of the most preferred network".to_string(),
+            _ => "SSID of the next network (none to finish)".to_string(),
+        };
+        let ssid = prompt.ask(&question, "", |ssid| match ssid {
+            "" if networks.is_empty() => Err("at least one network is needed".to_string()),
+            "" => Ok(String::new()),
+            ssid => check_network(ssid, WifiSecurity::Open, None).map(|network| network.ssid),
+        })?;
+        if ssid.is_empty() {
+            return Ok(networks);
+        }
+        let security = prompt.ask("Security: Wpa2 or Open", "Wpa2", parse_security)?;
+        let network = match security {
+            WifiSecurity::Open => NetworkSpec {
+                ssid,
+                password: None,
+                security: Some(security),
+            },
+            _ => prompt.ask("Password", "", |password| {
+                check_network(&ssid, security, Some(password))
+            })?,
+        };
+        networks.push(network);
+    }
+}
+
+// Parse the security of a network, which picomon can join
+fn parse_security(security: &str) -> Result<WifiSecurity, String> {
+    match toml::Value::String(security.to_string()).try_into() {
+        Ok(WifiSecurity::Wpa3) => Err("picomon can't join WPA3 networks yet".to_string()),
+        security => security.map_err(|e: toml::de::Error| e.to_string()),
+    }
+}
+
+// Check that a network could be joined
+fn check_network(
+    ssid: &str,
+    security: WifiSecurity,
+    password: Option<&str>,
+) -> Result<NetworkSpec, String> {
+    let network = NetworkSpec {
+        ssid: ssid.to_string(),
+        password: password.map(str::to_string),
+        security: Some(security),
+    };
+    match network.problem() {
+        Some(problem) => Err(format!("the network {problem}")),
+        None => Ok(network),
+    }
+}
+
+// Check a value for a setting, returning it as given with `--set` if it is valid
+fn check_setting(setting: &str, value: &str) -> Result<String, String> {
+    let setting_value = format!("{setting}={value}");
+    match config::load_config_as(
+        None,
+        std::slice::from_ref(&setting_value),
+        Identity::of,
+        None,
+    ) {
+        Ok(_) => Ok(setting_value),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Parse probe targets separated by commas, each of which must be "host:port"
+fn parse_targets(answer: &str) -> Result<Vec<String>, String> {
+    answer
+        .split(',')
+        .map(str::trim)
+        .filter(|target| !target.is_empty())
+        .map(|target| match target.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(target.to_string())
+            }
+            _ => Err(format!("'{target}' is not host:port")),
+        })
+        .collect()
+}
+
+// A value as TOML, e.g. a string quoted and escaped
+fn toml_value(value: &impl Serialize) -> String {
+    toml::Value::try_from(value)
+        .map(|value| value.to_string())
+        .unwrap_or_default()
+}
+
+// The text of a config file with the settings chosen, with comments describing them
+fn config_text(config: &Config) -> String {
+    let report = config.report.clone().unwrap_or_default();
+    let base_url = match &report.base_url {
+        Some(url) => format!("base_url = {}", toml_value(url)),
+        None => "#base_url = \"https://collectr.example.com\"".to_string(),
+    };
+    let probe = match config
+        .probe
+        .as_ref()
+        .and_then(|probe| probe.targets.as_ref())
+    {
+        Some(targets) => format!("[probe]\ntargets = {}", toml_value(targets)),
+        None => "#[probe]\n#targets = [\"1.1.1.1:53\"]".to_string(),
+    };
+    format!(
+        "# wimon config, written by 'wimon config init'. The README describes all the settings\n\
+         \n\
+         # Report on the \"Connection\" used to send reports, or on \"All\" the SSIDs seen\n\
+         monitor = {}\n\
+         \n\
+         [report]\n\
+         # The collector that reports are sent to. Without one they are printed\n\
+         {base_url}\n\
+         # Seconds between reports\n\
+         period_seconds = {}\n\
+         \n\
+         # TCP connections made to check connectivity beyond the local network, as \"host:port\"\n\
+         {probe}\n",
+        toml_value(&config.monitor.unwrap_or_default()),
+        report.period_seconds.unwrap_or(60),
+    )
+}
+
+// The text of an SSID file with the networks
+fn ssid_text(networks: Vec<NetworkSpec>) -> Result<String, io::Error> {
+    let spec = SsidSpec {
+        networks: Some(networks),
+        ..SsidSpec::default()
+    };
+    Ok(format!(
+        "# Wi-Fi networks for picomon, in order of preference, written by 'wimon config init'.\n\
+         # security is \"Wpa2\" (the default) or \"Open\"\n\n{}",
+        toml::to_string(&spec).map_err(io::Error::other)?
+    ))
+}
+
+/// Check the config, and the SSID file next to the config file if there is one. Then check
+/// that the collector's `base_url` resolves, and that the collector accepts a test report.
+/// An error is returned if any of the checks fail.
+pub fn check_config(
+    path: Option<&PathBuf>,
+    load: impl Fn() -> Result<LayeredConfig, ConfigError>,
+) -> Result<(), io::Error> {
+    let mut failed = 0;
+    let mut check = |name: &str, result: Result<String, io::Error>| match result {
+        Ok(detail) => {
+            println!("{name}: OK{detail}");
+            true
+        }
+        Err(e) => {
+            println!("{name}: FAILED: {e}");
+            failed += 1;
+            false
+        }
+    };
+
+    let config = load().map_err(io::Error::from);
+    let described = path.map(|path| format!(" ('{}')", path.display()));
+    let config = match config {
+        Ok(layered) => {
+            check("Config", Ok(described.unwrap_or_default()));
+            Some(layered.config)
+        }
+        Err(e) => {
+            check("Config", Err(e));
+            None
+        }
+    };
+
+    let ssid_path = path
+        .map(|path| path.with_file_name(SSID_FILE_NAME))
+        .filter(|ssid_path| ssid_path.exists());
+    if let Some(ssid_path) = ssid_path {
+        let result = config::read_ssid(&ssid_path).map(|spec| {
+            let count = spec.networks.map(|networks| networks.len()).unwrap_or(0)
+                + spec
+                    .ssid_name
+                    .iter()
+                    .filter(|ssid| !ssid.is_empty())
+                    .count();
+            format!(" ('{}', {count} network(s))", ssid_path.display())
+        });
+        check("SSID file", result.map_err(io::Error::from));
+    }
+
+    if let Some(config) = config {
+        if config.report_url.is_none() {
+            println!("Collector: none, as there is no base_url, so reports are printed");
+        } else if check("Collector address", resolve_collector(&config)) {
+            check("Test report", send_test_report(config));
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        failed => Err(io::Error::other(format!("{failed} check(s) failed"))),
+    }
+}
+
+// The addresses of the collector, unless reports are sent through a proxy, which resolves them
+fn resolve_collector(config: &Config) -> Result<String, io::Error> {
+    let Some(url) = &config.report_url else {
+        return Ok(String::new());
+    };
+    if let Some(proxy) = config.report.as_ref().and_then(|spec| spec.proxy.as_ref()) {
+        return Ok(format!(" (resolved by the proxy '{proxy}')"));
+    }
+    let addresses = url.socket_addrs(|| None).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!(
+                "'{}' could not be resolved: {e}",
+                url.host_str().unwrap_or_default()
+            ),
+        )
+    })?;
+    let addresses: Vec<_> = addresses.iter().map(ToString::to_string).collect();
+    Ok(format!(" ({})", addresses.join(", ")))
+}
+
+// Send an empty report to the collector, as the monitor does, so only the collector is checked
+// and not the measurements. It is followed by a Stop report, so the collector doesn't expect
+// another report one period later and mark the device Offline when none comes.
+fn send_test_report(config: Config) -> Result<String, io::Error> {
+    let mut monitor = Monitor::builder(config).without_default_sources().build()?;
+    let report = monitor.measure();
+    monitor.send(&ReportType::OnGoing, Some(CHECK_REASON), &report)?;
+    monitor.send(&ReportType::Stop, Some(CHECK_REASON), &report)?;
+    Ok(" (accepted by the collector)".to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::{init_config, parse_targets};
+    use crate::test_util::test_dir;
+
+    #[test]
+    fn targets_must_have_a_port() {
+        assert_eq!(
+            parse_targets("1.1.1.1:53, example.com:80,"),
+            Ok(vec!["1.1.1.1:53".to_string(), "example.com:80".to_string()])
+        );
+        assert!(parse_targets("1.1.1.1").is_err());
+        assert!(parse_targets(":53").is_err());
+    }
+
+    #[test]
+    fn init_asks_again_for_invalid_answers() {
+        let dir = test_dir("init");
+        let path = dir.join("monitor.toml");
+        let answers = "collectr.example.com\nhttps://collectr.example.com\n0\n30\nall\nAll\n\
+                       1.1.1.1\n1.1.1.1:53\ny\nHome\nWpa3\nWpa2\nshort\nhome-password\nCafe\nOpen\n\n";
+        let mut output = vec![];
+        init_config(&path, Cursor::new(answers), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("Not valid").count(), 6, "{output}");
+
+        let config = config::read_config(&path).unwrap();
+        assert_eq!(config.monitor, Some(config::MonitorSpec::All));
+        assert_eq!(config.period_duration.as_secs(), 30);
+        let networks = config::read_ssid(&dir.join("ssid.toml"))
+            .unwrap()
+            .networks
+            .unwrap();
+        assert_eq!(networks.len(), 2);
+        assert_eq!(networks[0].password, Some("home-password".to_string()));
+        assert_eq!(networks[1].security, Some(config::WifiSecurity::Open));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,263 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use config::{Config, IdStrategy};
+use data_model::{DeviceId, ReportType};
+use machineid_rs::{Encryption, HWIDComponent, IdBuilder};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::monitor::Monitor;
+
+const DEVICE_ID_FILE_NAME: &str = "wimon.device_id.json";
+
+/// The device ID in use, and the strategy used to create it, as persisted in the data directory
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct PersistedId {
+    strategy: IdStrategy,
+    id: DeviceId,
+}
+
+fn strategy(config: &Config) -> IdStrategy {
+    config
+        .device
+        .as_ref()
+        .and_then(|device| device.id_strategy)
+        .unwrap_or(IdStrategy::Hardware)
+}
+
+fn device_id_file_path(data_path: &Path) -> PathBuf {
+    data_path.join(DEVICE_ID_FILE_NAME)
+}
+
+fn load(data_path: &Path) -> Result<Option<PersistedId>, io::Error> {
+    match fs::read_to_string(device_id_file_path(data_path)) {
+        Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn save(data_path: &Path, persisted: &PersistedId) -> Result<(), io::Error> {
+    fs::create_dir_all(data_path)?;
+    fs::write(
+        device_id_file_path(data_path),
+        serde_json::to_string_pretty(persisted)?,
+    )
+}
+
+/// Get the ID of this device. The first time it is determined using the configured strategy
+/// and then persisted, so it stays the same even if the hardware or network interfaces change.
+/// It is only determined again if the strategy (or the explicit ID) in the config changes.
+pub fn get_device_id(config: &Config) -> Result<DeviceId, io::Error> {
+    let strategy = strategy(config);
+    if let Some(id) = persisted_id(config, strategy)? {
+        return Ok(id);
+    }
+
+    let id = new_device_id(config, strategy)?;
+    save(
+        &config.data_path,
+        &PersistedId {
+            strategy,
+            id: id.clone(),
+        },
+    )?;
+    Ok(id)
+}
+
+/// Find the ID of this device that [get_device_id] would get, without persisting it. A `Random`
+/// ID that has not been persisted yet is not known.
+pub fn find_device_id(config: &Config) -> Result<Option<DeviceId>, io::Error> {
+    let strategy = strategy(config);
+    match persisted_id(config, strategy)? {
+        Some(id) => Ok(Some(id)),
+        None if strategy == IdStrategy::Random => Ok(None),
+        None => new_device_id(config, strategy).map(Some),
+    }
+}
+
+// The persisted ID, unless the strategy (or the explicit ID) in the config has changed
+fn persisted_id(config: &Config, strategy: IdStrategy) -> Result<Option<DeviceId>, io::Error> {
+    let Some(persisted) = load(&config.data_path)? else {
+        return Ok(None);
+    };
+    let explicit_changed = strategy == IdStrategy::Explicit
+        && Some(&persisted.id) != config.device.as_ref().and_then(|d| d.id.as_ref());
+    Ok((persisted.strategy == strategy && !explicit_changed).then_some(persisted.id))
+}
+
+// Create a device ID using `strategy`. For all but `Random` the same ID will be created
+// while the hardware and config stay the same.
+fn new_device_id(config: &Config, strategy: IdStrategy) -> Result<DeviceId, io::Error> {
+    match strategy {
+        IdStrategy::Hardware => hardware_id(),
+        IdStrategy::Mac => mac_id(),
+        IdStrategy::Random => Ok(uuid::Uuid::new_v4().simple().to_string()),
+        IdStrategy::Explicit => config
+            .device
+            .as_ref()
+            .and_then(|device| device.id.clone())
+            .filter(|id| !id.trim().is_empty())
+            .ok_or(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The 'Explicit' id_strategy requires an 'id' in the [device] section of the config",
+            )),
+    }
+}
+
+fn hardware_id() -> Result<DeviceId, io::Error> {
+    let mut builder = IdBuilder::new(Encryption::SHA256);
+    builder
+        .add_component(HWIDComponent::CPUID)
+        .add_component(HWIDComponent::SystemID);
+    builder
+        .build("device_id")
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Could not build unique device_id"))
+}
+
+// Use the MAC address of the primary interface, as lower case hex digits without separators
+fn mac_id() -> Result<DeviceId, io::Error> {
+    let mac = primary_mac()?;
+    Ok(mac.replace(':', "").to_ascii_lowercase())
+}
+
+// The primary interface is the one used by the default route, if there is one, otherwise the
+// first interface that has a MAC address
+#[cfg(target_os = "linux")]
+fn primary_mac() -> Result<String, io::Error> {
+    let routes = fs::read_to_string("/proc/net/route").unwrap_or_default();
+    let mut interfaces: Vec<String> = routes
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let interface = fields.next()?;
+            (fields.next()? == "00000000").then(|| interface.to_string())
+        })
+        .collect();
+
+    let mut others: Vec<String> = fs::read_dir("/sys/class/net")?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    others.sort();
+    interfaces.extend(others);
+
+    for interface in interfaces {
+        let address =
+            fs::read_to_string(format!("/sys/class/net/{interface}/address")).unwrap_or_default();
+        let address = address.trim();
+        if !address.is_empty() && address != "00:00:00:00:00:00" {
+            return Ok(address.to_string());
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "Could not find a network interface with a MAC address",
+    ))
+}
+
+#[cfg(target_os = "macos")]
+fn primary_mac() -> Result<String, io::Error> {
+    let output = std::process::Command::new("/sbin/ifconfig")
+        .arg("en0")
+        .output()
+        .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))?;
+    let data = String::from_utf8_lossy(&output.stdout);
+    for line in data.lines() {
+        let mut pair = line.split_whitespace();
+        if pair.next() == Some("ether") {
+            if let Some(mac) = pair.next() {
+                return Ok(mac.to_string());
+            }
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "Could not find the MAC address of en0",
+    ))
+}
+
+/// Print the device ID in use, and how it was determined
+pub fn print_device_id(config: &Config) -> Result<(), io::Error> {
+    let id = get_device_id(config)?;
+    println!("Device ID = {id} (id_strategy = {:?})", strategy(config));
+    Ok(())
+}
+
+/// Rotate (for the `Random` strategy) or re-register (for the others) the ID of this device,
+/// by creating it again using the configured strategy and persisting it.
+/// If the ID changed, a Stop report is sent for the old ID so that the collector does not
+/// consider it to be Offline.
+pub fn rotate_device_id(config: &Config) -> Result<(), io::Error> {
+    let strategy = strategy(config);
+    let old_id = load(&config.data_path)?.map(|persisted| persisted.id);
+    let new_id = new_device_id(config, strategy)?;
+    save(
+        &config.data_path,
+        &PersistedId {
+            strategy,
+            id: new_id.clone(),
+        },
+    )?;
+
+    match old_id {
+        Some(old_id) if old_id != new_id => {
+            println!("Device ID changed from {old_id} to {new_id}");
+            let mut monitor = Monitor::builder(config.clone())
+                .device_id(&old_id)
+                .build()?;
+            let report = monitor.measure();
+            monitor.send(&ReportType::Stop, Some("rotate"), &report)?;
+        }
+        _ => println!("Device ID = {new_id}"),
+    }
+    println!("Restart wimon (or its service) for it to report using this ID");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use config::{Config, DeviceSpec, IdStrategy};
+
+    use super::{find_device_id, get_device_id};
+    use crate::test_util::test_dir;
+
+    fn config(name: &str, id_strategy: IdStrategy, id: Option<&str>) -> Config {
+        let data_path = test_dir(name);
+        Config {
+            device: Some(DeviceSpec {
+                id_strategy: Some(id_strategy),
+                id: id.map(|id| id.to_string()),
+                ..Default::default()
+            }),
+            data_path,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn random_id_is_persisted() {
+        let config = config("random-id", IdStrategy::Random, None);
+        // Finding the ID doesn't create one
+        assert_eq!(find_device_id(&config).unwrap(), None);
+        let id = get_device_id(&config).unwrap();
+        assert_eq!(find_device_id(&config).unwrap(), Some(id.clone()));
+        assert_eq!(id.len(), 32);
+        assert_eq!(get_device_id(&config).unwrap(), id);
+        std::fs::remove_dir_all(&config.data_path).unwrap();
+    }
+
+    #[test]
+    fn explicit_id_change_is_used() {
+        let config_a = config("explicit-id", IdStrategy::Explicit, Some("site-a"));
+        assert_eq!(get_device_id(&config_a).unwrap(), "site-a");
+        let config_b = config("explicit-id", IdStrategy::Explicit, Some("site-b"));
+        assert_eq!(get_device_id(&config_b).unwrap(), "site-b");
+        std::fs::remove_dir_all(&config_b.data_path).unwrap();
+    }
+}
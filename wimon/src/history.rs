@@ -0,0 +1,279 @@
+use std::io;
+use std::time::Duration;
+
+use config::Config;
+use data_model::{MonitorReport, ReportType};
+use rusqlite::{params, Connection};
+use serde_derive::Serialize;
+
+use crate::state::now;
+
+const HISTORY_FILE_NAME: &str = "wimon.history.sqlite";
+const DEFAULT_RETENTION_DAYS: u64 = 30;
+
+/// A measurement as recorded in the history
+#[derive(Serialize, Debug)]
+pub(crate) struct HistoryEntry {
+    pub timestamp: u64, // seconds in Unix EPOCH
+    pub report_type: String,
+    pub connection: String,
+    pub sent: bool,
+    pub report: MonitorReport,
+}
+
+/// Which entries in the history to select
+#[derive(Default, Debug)]
+pub struct HistoryFilter {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub connection: Option<String>,
+}
+
+/// [History] is a rolling history of the measurements taken, kept in an SQLite database in the
+/// data directory. Measurements older than the configured retention are deleted.
+pub(crate) struct History {
+    connection: Connection,
+    retention: Duration,
+}
+
+fn db_error(e: rusqlite::Error) -> io::Error {
+    io::Error::other(format!("History database error: {e}"))
+}
+
+impl History {
+    /// Open (creating it if needed) the history in the data directory, unless it is disabled
+    pub fn open(config: &Config) -> Result<Option<Self>, io::Error> {
+        let spec = config.history.as_ref();
+        if !spec.and_then(|s| s.enabled).unwrap_or(true) {
+            return Ok(None);
+        }
+        let retention_days = spec
+            .and_then(|s| s.retention_days)
+            .unwrap_or(DEFAULT_RETENTION_DAYS);
+
+        std::fs::create_dir_all(&config.data_path)?;
+        let connection =
+            Connection::open(config.data_path.join(HISTORY_FILE_NAME)).map_err(db_error)?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS measurements (
+                    timestamp INTEGER NOT NULL,
+                    report_type TEXT NOT NULL,
+                    connection TEXT NOT NULL,
+                    sent INTEGER NOT NULL,
+                    report TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS measurements_timestamp ON measurements (timestamp);",
+            )
+            .map_err(db_error)?;
+
+        Ok(Some(History {
+            connection,
+            retention: Duration::from_secs(retention_days * 24 * 60 * 60),
+        }))
+    }
+
+    /// Record a measurement, and whether it was sent successfully, deleting any that are now
+    /// older than the retention period
+    pub fn record(
+        &self,
+        timestamp: u64,
+        report_type: &ReportType,
+        sent: bool,
+        report: &MonitorReport,
+    ) -> Result<(), io::Error> {
+        self.connection
+            .execute(
+                "INSERT INTO measurements (timestamp, report_type, connection, sent, report)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    timestamp,
+                    report_type.to_string(),
+                    report.connection_used.to_string().trim(),
+                    sent,
+                    serde_json::to_string(report)?
+                ],
+            )
+            .map_err(db_error)?;
+
+        self.connection
+            .execute(
+                "DELETE FROM measurements WHERE timestamp < ?1",
+                params![timestamp.saturating_sub(self.retention.as_secs())],
+            )
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    /// Get the entries that match `filter`, oldest first. A connection matches if it is the
+    /// same as the connection in the filter, with or without the type, e.g. "ssid=MyWifi" or "MyWifi"
+    pub fn query(&self, filter: &HistoryFilter) -> Result<Vec<HistoryEntry>, io::Error> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT timestamp, report_type, connection, sent, report FROM measurements
+                WHERE timestamp >= ?1 AND timestamp <= ?2
+                AND (?3 IS NULL OR connection = ?3 OR connection LIKE '%=' || ?3)
+                ORDER BY timestamp",
+            )
+            .map_err(db_error)?;
+
+        let rows = statement
+            .query_map(
+                params![
+                    filter.from.unwrap_or(0),
+                    filter.to.unwrap_or(i64::MAX as u64),
+                    filter.connection
+                ],
+                |row| {
+                    let report: String = row.get(4)?;
+                    Ok(HistoryEntry {
+                        timestamp: row.get(0)?,
+                        report_type: row.get(1)?,
+                        connection: row.get(2)?,
+                        sent: row.get(3)?,
+                        report: serde_json::from_str(&report).unwrap_or_default(),
+                    })
+                },
+            )
+            .map_err(db_error)?;
+
+        rows.collect::<Result<Vec<_>, _>>().map_err(db_error)
+    }
+}
+
+/// Parse a time given on the command line, either as seconds in the Unix EPOCH, or as a time
+/// relative to now such as "90s", "30m", "12h" or "7d" (ago)
+pub fn parse_time(time: &str) -> Result<u64, io::Error> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Invalid time '{time}', use seconds in Unix EPOCH or a relative time e.g. '12h'"
+            ),
+        )
+    };
+
+    if let Ok(seconds) = time.parse::<u64>() {
+        return Ok(seconds);
+    }
+
+    let split = time.len().checked_sub(1).ok_or_else(invalid)?;
+    let (number, unit) = time.split_at(split);
+    let number = number.parse::<u64>().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "s" => number,
+        "m" => number * 60,
+        "h" => number * 60 * 60,
+        "d" => number * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    Ok(now().saturating_sub(seconds))
+}
+
+// Quote a field for CSV output if needed
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Print the entries in the history that match `filter` in `format` ("text", "csv" or "json")
+pub fn print_history(
+    config: &Config,
+    filter: &HistoryFilter,
+    format: &str,
+) -> Result<(), io::Error> {
+    let history = History::open(config)?.ok_or(io::Error::new(
+        io::ErrorKind::NotFound,
+        "Measurement history is disabled in the config",
+    ))?;
+    let entries = history.query(filter)?;
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&entries)?),
+        "csv" => {
+            println!("timestamp,report_type,connection,sent,report");
+            for entry in entries {
+                println!(
+                    "{},{},{},{},{}",
+                    entry.timestamp,
+                    entry.report_type,
+                    csv_field(&entry.connection),
+                    entry.sent,
+                    csv_field(&serde_json::to_string(&entry.report)?)
+                );
+            }
+        }
+        "text" => {
+            for entry in entries {
+                println!(
+                    "{} {:<8} {:<32} {}",
+                    entry.timestamp,
+                    entry.report_type,
+                    entry.connection,
+                    if entry.sent { "sent" } else { "NOT SENT" }
+                );
+            }
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unknown format '{format}', use 'text', 'csv' or 'json'"),
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use config::Config;
+    use data_model::{Connection, MonitorReport, ReportType};
+
+    use super::{History, HistoryFilter};
+    use crate::test_util::test_dir;
+
+    #[test]
+    fn record_and_query() {
+        let data_path = test_dir("history");
+        let config = Config {
+            data_path: data_path.clone(),
+            ..Default::default()
+        };
+        let history = History::open(&config).unwrap().unwrap();
+        let report = MonitorReport {
+            connection_used: Connection::SSID("MyWifi".into()),
+            connections: vec![],
+            probes: vec![],
+            paths: vec![],
+            addressing: None,
+        };
+        history
+            .record(1000, &ReportType::OnGoing, true, &report)
+            .unwrap();
+        history
+            .record(2000, &ReportType::OnGoing, false, &report)
+            .unwrap();
+        history
+            .record(3000, &ReportType::Stop, true, &MonitorReport::default())
+            .unwrap();
+
+        let filter = HistoryFilter {
+            from: Some(1500),
+            connection: Some("MyWifi".into()),
+            ..Default::default()
+        };
+        let entries = history.query(&filter).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].timestamp, 2000);
+        assert_eq!(entries[0].connection, "ssid=MyWifi");
+        assert!(!entries[0].sent);
+
+        std::fs::remove_dir_all(&data_path).unwrap();
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::HashSet;
+use std::io;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use config::{Config, HookEvent, HookSpec};
+use data_model::{DeviceId, MonitorReport};
+
+const DEFAULT_THRESHOLD: u32 = 3;
+const DEFAULT_TIMEOUT_SECONDS: u64 = 30;
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A connectivity event detected from the reports, with its details
+#[derive(Debug)]
+struct Event {
+    kind: HookEvent,
+    /// The number of consecutive report failures, for `ReportFailures` and `ReportRecovered`
+    failures: u32,
+    env: Vec<(&'static str, String)>,
+}
+
+/// [Hooks] detects connectivity events from the reports sent and runs the configured hook
+/// commands for them, passing the details of the event as environment variables
+pub(crate) struct Hooks {
+    specs: Vec<HookSpec>,
+    device_id: DeviceId,
+    failures: u32,
+    connection: Option<String>,
+    failing_probes: HashSet<String>,
+}
+
+impl Hooks {
+    /// Create [Hooks] if any are configured
+    pub fn new(config: &Config, device_id: &DeviceId) -> Option<Self> {
+        let specs = config.hooks.clone().filter(|hooks| !hooks.is_empty())?;
+        Some(Hooks {
+            specs,
+            device_id: device_id.clone(),
+            failures: 0,
+            connection: None,
+            failing_probes: HashSet::new(),
+        })
+    }
+
+    /// Detect events from a report and the result of sending it, and run the hooks for them
+    pub fn after_report(&mut self, report: &MonitorReport, result: &Result<(), io::Error>) {
+        for event in self.detect(report, result) {
+            for spec in self.specs.iter().filter(|spec| triggers(spec, &event)) {
+                let mut env = event.env.clone();
+                env.push(("WIMON_EVENT", format!("{:?}", event.kind)));
+                env.push(("WIMON_DEVICE_ID", self.device_id.clone()));
+                run(spec.clone(), env);
+            }
+        }
+    }
+
+    fn detect(&mut self, report: &MonitorReport, result: &Result<(), io::Error>) -> Vec<Event> {
+        let mut events = vec![];
+        let connection = report.connection_used.to_string().trim().to_string();
+
+        match result {
+            Ok(_) => {
+                if self.failures > 0 {
+                    events.push(Event {
+                        kind: HookEvent::ReportRecovered,
+                        failures: self.failures,
+                        env: vec![
+                            ("WIMON_CONNECTION", connection.clone()),
+                            ("WIMON_FAILURES", self.failures.to_string()),
+                        ],
+                    });
+                }
+                self.failures = 0;
+            }
+            Err(e) => {
+                self.failures += 1;
+                events.push(Event {
+                    kind: HookEvent::ReportFailures,
+                    failures: self.failures,
+                    env: vec![
+                        ("WIMON_CONNECTION", connection.clone()),
+                        ("WIMON_FAILURES", self.failures.to_string()),
+                        ("WIMON_ERROR", e.to_string()),
+                    ],
+                });
+            }
+        }
+
+        if let Some(previous) = self.connection.as_ref().filter(|c| **c != connection) {
+            events.push(Event {
+                kind: HookEvent::SsidChanged,
+                failures: 0,
+                env: vec![
+                    ("WIMON_CONNECTION", connection.clone()),
+                    ("WIMON_PREVIOUS_CONNECTION", previous.clone()),
+                ],
+            });
+        }
+        self.connection = Some(connection.clone());
+
+        for probe in &report.probes {
+            let failed = probe.latency_ms.is_none();
+            let was_failing = self.failing_probes.contains(&probe.target);
+            let kind = match (failed, was_failing) {
+                (true, false) => HookEvent::ProbeFailed,
+                (false, true) => HookEvent::ProbeRecovered,
+                _ => continue,
+            };
+            let mut env = vec![
+                ("WIMON_CONNECTION", connection.clone()),
+                ("WIMON_PROBE_TARGET", probe.target.clone()),
+            ];
+            if let Some(latency) = probe.latency_ms {
+                env.push(("WIMON_PROBE_LATENCY_MS", latency.to_string()));
+            }
+            events.push(Event {
+                kind,
+                failures: 0,
+                env,
+            });
+            if failed {
+                self.failing_probes.insert(probe.target.clone());
+            } else {
+                self.failing_probes.remove(&probe.target);
+            }
+        }
+
+        if let Some(addressing) = report
+            .addressing
+            .as_ref()
+            .filter(|addressing| !addressing.changed.is_empty())
+        {
+            let mut env = vec![
+                ("WIMON_CONNECTION", connection.clone()),
+                ("WIMON_ADDRESSING_CHANGED", addressing.changed.join(",")),
+            ];
+            if let Some(public_ip) = &addressing.public_ip {
+                env.push(("WIMON_PUBLIC_IP", public_ip.clone()));
+            }
+            if let Some(gateway) = &addressing.gateway {
+                env.push(("WIMON_GATEWAY", gateway.clone()));
+            }
+            events.push(Event {
+                kind: HookEvent::AddressingChanged,
+                failures: 0,
+                env,
+            });
+        }
+
+        events
+    }
+}
+
+// Does the event trigger the hook? Failure streaks trigger once, when they reach the threshold
+fn triggers(spec: &HookSpec, event: &Event) -> bool {
+    let threshold = spec.threshold.unwrap_or(DEFAULT_THRESHOLD);
+    spec.event == event.kind
+        && match event.kind {
+            HookEvent::ReportFailures => event.failures == threshold,
+            HookEvent::ReportRecovered => event.failures >= threshold,
+            _ => true,
+        }
+}
+
+// Run the hook's command in the background so it doesn't delay reporting, killing it if it
+// runs for longer than its timeout. Its output goes to wimon's output, and the outcome is logged.
+fn run(spec: HookSpec, env: Vec<(&'static str, String)>) {
+    thread::spawn(move || {
+        let event = format!("{:?}", spec.event);
+        let timeout = Duration::from_secs(spec.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS));
+        println!("Running hook '{}' for {event}", spec.command);
+
+        let mut child = match Command::new(&spec.command)
+            .args(spec.args.as_deref().unwrap_or_default())
+            .envs(env)
+            .stdin(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("Could not run hook '{}' for {event}: {e}", spec.command);
+                return;
+            }
+        };
+
+        let start = Instant::now();
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => {
+                    println!("Hook '{}' for {event} succeeded", spec.command);
+                    return;
+                }
+                Ok(Some(status)) => {
+                    eprintln!("Hook '{}' for {event} failed: {status}", spec.command);
+                    return;
+                }
+                Ok(None) if start.elapsed() >= timeout => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    eprintln!(
+                        "Hook '{}' for {event} timed out after {}s and was killed",
+                        spec.command,
+                        timeout.as_secs()
+                    );
+                    return;
+                }
+                Ok(None) => thread::sleep(POLL_INTERVAL),
+                Err(e) => {
+                    eprintln!(
+                        "Could not wait for hook '{}' for {event}: {e}",
+                        spec.command
+                    );
+                    return;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use config::{Config, HookEvent, HookSpec};
+    use data_model::{Connection, MonitorReport, ProbeReport};
+
+    use super::{triggers, Hooks};
+
+    fn report(ssid: &str, latency_ms: Option<u32>) -> MonitorReport {
+        MonitorReport {
+            connection_used: Connection::SSID(ssid.into()),
+            connections: vec![],
+            probes: vec![ProbeReport {
+                target: "1.1.1.1:53".into(),
+                latency_ms,
+                samples: None,
+            }],
+            paths: vec![],
+            addressing: None,
+        }
+    }
+
+    #[test]
+    fn detect_events() {
+        let spec = HookSpec {
+            event: HookEvent::ReportFailures,
+            command: "true".into(),
+            args: None,
+            threshold: Some(2),
+            timeout_seconds: None,
+        };
+        let config = Config {
+            hooks: Some(vec![spec.clone()]),
+            ..Default::default()
+        };
+        let mut hooks = Hooks::new(&config, &"device".to_string()).unwrap();
+        let failed = || Err(io::Error::new(io::ErrorKind::NotFound, "failed"));
+
+        assert!(hooks.detect(&report("A", Some(10)), &Ok(())).is_empty());
+
+        let events = hooks.detect(&report("A", None), &failed());
+        let kinds: Vec<HookEvent> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, [HookEvent::ReportFailures, HookEvent::ProbeFailed]);
+        assert!(!triggers(&spec, &events[0]));
+
+        let events = hooks.detect(&report("A", None), &failed());
+        assert!(triggers(&spec, &events[0]));
+
+        let events = hooks.detect(&report("B", Some(10)), &Ok(()));
+        let kinds: Vec<HookEvent> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                HookEvent::ReportRecovered,
+                HookEvent::SsidChanged,
+                HookEvent::ProbeRecovered
+            ]
+        );
+        assert!(events[1]
+            .env
+            .contains(&("WIMON_PREVIOUS_CONNECTION", "ssid=A".to_string())));
+    }
+}
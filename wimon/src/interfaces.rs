@@ -0,0 +1,192 @@
+use std::io;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::process::Command;
+
+use config::Config;
+use data_model::{Connection, ConnectionReport, MonitorReport, Stats};
+
+use crate::monitor::Source;
+use crate::probe::ProbeSource;
+use crate::wifi::get_link;
+
+/// [InterfaceSource] runs the probes bound to each of the interfaces in the config, reporting a
+/// connection per interface so that the paths (e.g. Wi-Fi and Ethernet) can be compared.
+/// The connection used is set to that of the interface the route to the collector goes through.
+pub struct InterfaceSource {
+    interfaces: Vec<String>,
+    probes: ProbeSource,
+    collector: Option<(String, u16)>,
+}
+
+impl InterfaceSource {
+    /// Create an [InterfaceSource] if any interfaces are configured
+    pub fn new(config: &Config) -> Option<Self> {
+        let interfaces = config
+            .probe
+            .as_ref()
+            .and_then(|spec| spec.interfaces.clone())
+            .filter(|interfaces| !interfaces.is_empty())?;
+        let collector = config
+            .report_url
+            .as_ref()
+            .and_then(|url| Some((url.host_str()?.to_string(), url.port_or_known_default()?)));
+
+        Some(InterfaceSource {
+            interfaces,
+            probes: ProbeSource::new(config),
+            collector,
+        })
+    }
+}
+
+impl Source for InterfaceSource {
+    fn measure(&mut self, report: &mut MonitorReport) -> Result<(), io::Error> {
+        let route = self.collector.as_ref().and_then(|(host, port)| {
+            route_to(host, *port)
+                .map_err(|e| eprintln!("Could not get the route to '{host}': {e}"))
+                .ok()
+        });
+
+        for interface in &self.interfaces {
+            let (connection, signal) = match get_link(interface) {
+                Ok((ssid, signal)) => (Connection::SSID(ssid), signal),
+                Err(_) => (Connection::Ethernet(interface.clone()), None),
+            };
+            if route.as_ref().is_some_and(|route| route.is(interface)) {
+                report.connection_used = connection.clone();
+            }
+            let probes = self.probes.run_via(Some(interface));
+
+            // A Wi-Fi connection may already have been reported by a scan
+            let name = connection.to_string();
+            match report
+                .connections
+                .iter_mut()
+                .find(|c| c.connection.to_string() == name && c.interface.is_none())
+            {
+                Some(existing) => {
+                    existing.interface = Some(interface.clone());
+                    existing.probes = probes;
+                }
+                None => report.connections.push(ConnectionReport {
+                    connection,
+                    stats: signal.map(|power_dbs| Stats {
+                        power_dbs,
+                        samples: None,
+                    }),
+                    access_points: vec![],
+                    interface: Some(interface.clone()),
+                    probes,
+                    link: None,
+                }),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The interface, and source address if known, that traffic to a destination goes through
+#[derive(Debug, PartialEq)]
+struct Route {
+    interface: String,
+    source: Option<IpAddr>,
+}
+
+impl Route {
+    // Is this route through `interface`, given by name or address?
+    fn is(&self, interface: &str) -> bool {
+        self.interface == interface
+            || (self.source.is_some() && self.source == interface.parse().ok())
+    }
+}
+
+// Ask the OS which interface it would route traffic to `host` through
+fn route_to(host: &str, port: u16) -> Result<Route, io::Error> {
+    let address = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Could not resolve '{host}'"),
+        ))?;
+
+    #[cfg(target_os = "linux")]
+    let output = Command::new("ip")
+        .args(["route", "get", &address.ip().to_string()])
+        .output()
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Could not execute 'ip'"))?;
+    #[cfg(target_os = "macos")]
+    let output = Command::new("/sbin/route")
+        .args(["-n", "get", &address.ip().to_string()])
+        .output()
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Could not execute 'route'"))?;
+
+    parse_route(&String::from_utf8_lossy(&output.stdout))
+}
+
+// Parse the output of `ip route get`, e.g. "1.1.1.1 via 192.168.1.1 dev wlan0 src 192.168.1.23"
+#[cfg(target_os = "linux")]
+fn parse_route(data: &str) -> Result<Route, io::Error> {
+    let words: Vec<&str> = data.split_whitespace().collect();
+    let after = |label: &str| {
+        words
+            .iter()
+            .position(|word| *word == label)
+            .and_then(|position| words.get(position + 1))
+    };
+
+    Ok(Route {
+        interface: after("dev")
+            .ok_or(io::Error::new(
+                io::ErrorKind::NotFound,
+                "Could not parse route",
+            ))?
+            .to_string(),
+        source: after("src").and_then(|source| source.parse().ok()),
+    })
+}
+
+// Parse the output of `route -n get`, which includes e.g. "interface: en0"
+#[cfg(target_os = "macos")]
+fn parse_route(data: &str) -> Result<Route, io::Error> {
+    data.lines()
+        .find_map(|line| line.trim().strip_prefix("interface:"))
+        .map(|interface| Route {
+            interface: interface.trim().to_string(),
+            source: None,
+        })
+        .ok_or(io::Error::new(
+            io::ErrorKind::NotFound,
+            "Could not parse route",
+        ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::Route;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parse_route() {
+        let route = super::parse_route(
+            "1.1.1.1 via 192.168.1.1 dev wlan0 src 192.168.1.23 uid 1000 \n    cache \n",
+        )
+        .unwrap();
+        assert_eq!(route.interface, "wlan0");
+        assert!(route.is("wlan0"));
+        assert!(route.is("192.168.1.23"));
+        assert!(!route.is("eth0"));
+        assert!(super::parse_route("").is_err());
+    }
+
+    #[test]
+    fn route_without_source() {
+        let route = Route {
+            interface: "en0".into(),
+            source: None,
+        };
+        assert!(route.is("en0"));
+        assert!(!route.is("192.168.1.23"));
+    }
+}
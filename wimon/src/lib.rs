@@ -0,0 +1,52 @@
+//! `wimon` monitors the connectivity of a device and reports it to a collector.
+//!
+//! It can be embedded in another application by building a [Monitor] from a config, optionally
+//! adding custom [Source]s of measurements and [Sink]s to send reports to, then either taking
+//! and sending one measurement, or starting it to report periodically in the background.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), std::io::Error> {
+//! let config = config::read_config(&"monitor.toml".into())?;
+//! let handle = wimon::Monitor::builder(config).build()?.start()?;
+//! // ...
+//! handle.stop()?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod adaptive;
+mod addressing;
+mod collector;
+pub mod device_id;
+pub mod history;
+mod hooks;
+mod interfaces;
+#[cfg(target_os = "linux")]
+mod link;
+mod monitor;
+mod power;
+mod probe;
+pub mod remote;
+mod sampling;
+mod schedule;
+mod simulation;
+pub mod state;
+#[cfg(feature = "ssids")]
+mod survey;
+#[cfg(test)]
+mod test_util;
+mod trace;
+mod transport;
+mod wifi;
+
+pub use addressing::AddressingSource;
+pub use collector::CollectorSink;
+pub use interfaces::InterfaceSource;
+#[cfg(target_os = "linux")]
+pub use link::LinkSource;
+pub use monitor::{
+    Monitor, MonitorBuilder, MonitorHandle, Reconfigurer, Report, Sink, Source, Stopper,
+};
+pub use probe::ProbeSource;
+pub use trace::TraceSource;
+pub use wifi::WifiSource;
@@ -0,0 +1,307 @@
+use std::io;
+use std::process::Command;
+use std::str::FromStr;
+
+use data_model::{ConnectionReport, LinkStats, MonitorReport, Stats};
+
+use crate::monitor::Source;
+use crate::wifi::get_connected_interface;
+
+const PROC_NET_WIRELESS: &str = "/proc/net/wireless";
+
+/// [LinkSource] adds statistics of the wireless link in use to the connection used: the bitrates
+/// and MCS, and the tx retries, tx failures and beacons lost during the period. They are read
+/// from the kernel's nl80211 station info (using `iw`), or from `/proc/net/wireless` (which only
+/// has the packets discarded after too many retries, i.e. the tx failures, and the beacons lost)
+/// if that is not available.
+#[derive(Default)]
+pub struct LinkSource {
+    previous: Option<Counters>,
+}
+
+/// The link's counters at the end of the previous period
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Counters {
+    interface: String,
+    /// The access point (BSSID) connected to, if known
+    station: Option<String>,
+    connected_seconds: Option<u64>,
+    tx_retries: Option<u64>,
+    tx_failed: Option<u64>,
+    beacon_loss: Option<u64>,
+}
+
+/// A reading of the link's statistics, with the counters as totals
+#[derive(Debug, Default, PartialEq)]
+struct Reading {
+    station: Option<String>,
+    signal: Option<i16>,
+    stats: LinkStats,
+}
+
+impl LinkSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Replace the totals of the counters in `reading` with the change since the previous reading.
+    // There is no previous reading to compare with after (re)connecting, or changing access point.
+    fn deltas(&mut self, interface: &str, reading: Reading) -> (Option<i16>, LinkStats) {
+        let mut stats = reading.stats;
+        let current = Counters {
+            interface: interface.to_string(),
+            station: reading.station,
+            connected_seconds: stats.connected_seconds,
+            tx_retries: stats.tx_retries,
+            tx_failed: stats.tx_failed,
+            beacon_loss: stats.beacon_loss,
+        };
+        let previous = self.previous.take().filter(|previous| {
+            previous.interface == current.interface
+                && previous.station == current.station
+                && previous.connected_seconds <= current.connected_seconds
+        });
+        let delta = |previous: Option<u64>, current: Option<u64>| current?.checked_sub(previous?);
+        stats.tx_retries = previous
+            .as_ref()
+            .and_then(|previous| delta(previous.tx_retries, current.tx_retries));
+        stats.tx_failed = previous
+            .as_ref()
+            .and_then(|previous| delta(previous.tx_failed, current.tx_failed));
+        stats.beacon_loss = previous
+            .as_ref()
+            .and_then(|previous| delta(previous.beacon_loss, current.beacon_loss));
+        self.previous = Some(current);
+        (reading.signal, stats)
+    }
+}
+
+impl Source for LinkSource {
+    fn measure(&mut self, report: &mut MonitorReport) -> Result<(), io::Error> {
+        let Ok(interface) = get_connected_interface() else {
+            // Not connected to Wi-Fi, so there is no wireless link to report on
+            self.previous = None;
+            return Ok(());
+        };
+        let reading = match read_station(&interface) {
+            Ok(reading) => reading,
+            Err(_) => match read_proc_net_wireless(&interface) {
+                Ok(reading) => reading,
+                Err(e) => {
+                    // The link stats are optional, so report the rest without them
+                    eprintln!("Could not read link stats of '{interface}': {e}");
+                    self.previous = None;
+                    return Ok(());
+                }
+            },
+        };
+        let (signal, link) = self.deltas(&interface, reading);
+        add_link_stats(report, signal, link);
+        Ok(())
+    }
+}
+
+// Add the link's statistics to the connection used, and its signal level if the connection has
+// no stats yet. The link's statistics are kept even if the signal level could not be read.
+fn add_link_stats(report: &mut MonitorReport, signal: Option<i16>, link: LinkStats) {
+    let stats = signal.map(|power_dbs| Stats {
+        power_dbs,
+        samples: None,
+    });
+    let used = report.connection_used.to_string();
+    match report
+        .connections
+        .iter_mut()
+        .find(|c| c.connection.to_string() == used)
+    {
+        Some(connection) => {
+            if connection.stats.is_none() {
+                connection.stats = stats;
+            }
+            connection.link = Some(link);
+        }
+        None => report.connections.push(ConnectionReport {
+            connection: report.connection_used.clone(),
+            stats,
+            access_points: vec![],
+            interface: None,
+            probes: vec![],
+            link: Some(link),
+        }),
+    }
+}
+
+fn read_station(interface: &str) -> Result<Reading, io::Error> {
+    let output = Command::new("iw")
+        .args(["dev", interface, "station", "dump"])
+        .output()
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Could not execute 'iw'"))?;
+    parse_station_dump(&String::from_utf8_lossy(&output.stdout))
+}
+
+// Parse the output of `iw dev <interface> station dump`, for the station (access point) connected
+// to, e.g. "Station aa:bb:cc:dd:ee:ff (on wlan0)" followed by lines of "label: value"
+fn parse_station_dump(data: &str) -> Result<Reading, io::Error> {
+    let mut lines = data.lines();
+    let station = lines
+        .next()
+        .and_then(|line| line.strip_prefix("Station "))
+        .and_then(|rest| rest.split_whitespace().next())
+        .ok_or(io::Error::new(
+            io::ErrorKind::NotFound,
+            "Could not parse station info",
+        ))?;
+
+    let mut reading = Reading {
+        station: Some(station.to_ascii_lowercase()),
+        ..Default::default()
+    };
+    // Only the first station is used, which is the access point when connected to one
+    for line in lines.take_while(|line| !line.starts_with("Station ")) {
+        let Some((label, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match label {
+            "signal" => reading.signal = first_number(value),
+            "tx bitrate" => {
+                reading.stats.tx_bitrate_mbps = first_number(value);
+                reading.stats.tx_mcs = parse_mcs(value);
+            }
+            "rx bitrate" => {
+                reading.stats.rx_bitrate_mbps = first_number(value);
+                reading.stats.rx_mcs = parse_mcs(value);
+            }
+            "tx retries" => reading.stats.tx_retries = first_number(value),
+            "tx failed" => reading.stats.tx_failed = first_number(value),
+            "beacon loss" => reading.stats.beacon_loss = first_number(value),
+            "connected time" => reading.stats.connected_seconds = first_number(value),
+            _ => {}
+        }
+    }
+    Ok(reading)
+}
+
+// Parse the number at the start of a value, e.g. "-54 [-54, -56] dBm"
+fn first_number<T: FromStr>(value: &str) -> Option<T> {
+    value.split_whitespace().next()?.parse().ok()
+}
+
+// Parse the MCS index from a bitrate, e.g. "433.3 MBit/s VHT-MCS 9 80MHz short GI VHT-NSS 1"
+fn parse_mcs(bitrate: &str) -> Option<u8> {
+    let mut words = bitrate.split_whitespace();
+    words.position(|word| word == "MCS" || word.ends_with("-MCS"))?;
+    words.next()?.parse().ok()
+}
+
+fn read_proc_net_wireless(interface: &str) -> Result<Reading, io::Error> {
+    parse_proc_net_wireless(&std::fs::read_to_string(PROC_NET_WIRELESS)?, interface)
+}
+
+// Parse the line for `interface` in /proc/net/wireless, e.g.
+// "wlan0: 0000   54.  -56.  -256        0      0      0      5      0        3"
+// with the status, link quality, level, noise, discarded packets (nwid, crypt, frag, retry, misc)
+// and missed beacons
+fn parse_proc_net_wireless(data: &str, interface: &str) -> Result<Reading, io::Error> {
+    let words: Vec<&str> = data
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(&format!("{interface}:")))
+        .ok_or(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Interface '{interface}' not found in wireless statistics"),
+        ))?
+        .split_whitespace()
+        .collect();
+    let number = |index: usize| {
+        words
+            .get(index)
+            .and_then(|word| word.trim_end_matches('.').parse::<i64>().ok())
+    };
+
+    Ok(Reading {
+        station: None,
+        signal: number(2).map(|level| level as i16),
+        // Packets discarded because of retries are the ones that failed after all their retries
+        stats: LinkStats {
+            tx_failed: number(7).map(|discarded| discarded as u64),
+            beacon_loss: number(9).map(|missed| missed as u64),
+            ..Default::default()
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use data_model::{Connection, LinkStats, MonitorReport};
+
+    use super::{add_link_stats, parse_proc_net_wireless, parse_station_dump, LinkSource};
+
+    const STATION_DUMP: &str = "Station AA:BB:CC:DD:EE:FF (on wlan0)\n\
+        \tinactive time:\t40 ms\n\trx bytes:\t123456\n\ttx retries:\t120\n\ttx failed:\t4\n\
+        \tbeacon loss:\t1\n\tsignal:  \t-54 [-54, -56] dBm\n\
+        \ttx bitrate:\t433.3 MBit/s VHT-MCS 9 80MHz short GI VHT-NSS 1\n\
+        \trx bitrate:\t72.2 MBit/s MCS 7 short GI\n\tconnected time:\t3600 seconds\n";
+
+    #[test]
+    fn parse_station() {
+        let reading = parse_station_dump(STATION_DUMP).unwrap();
+        assert_eq!(reading.station.as_deref(), Some("aa:bb:cc:dd:ee:ff"));
+        assert_eq!(reading.signal, Some(-54));
+        assert_eq!(reading.stats.tx_bitrate_mbps, Some(433.3));
+        assert_eq!(reading.stats.tx_mcs, Some(9));
+        assert_eq!(reading.stats.rx_mcs, Some(7));
+        assert_eq!(reading.stats.tx_retries, Some(120));
+        assert_eq!(reading.stats.connected_seconds, Some(3600));
+        assert!(parse_station_dump("").is_err());
+
+        let proc =
+            "Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE\n \
+        face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22\n \
+        wlan0: 0000   54.  -56.  -256        0      0      0      5      0        3\n";
+        let reading = parse_proc_net_wireless(proc, "wlan0").unwrap();
+        assert_eq!(reading.signal, Some(-56));
+        assert_eq!(reading.stats.tx_failed, Some(5));
+        assert_eq!(reading.stats.tx_retries, None);
+        assert_eq!(reading.stats.beacon_loss, Some(3));
+    }
+
+    #[test]
+    fn counters_are_per_period() {
+        let mut source = LinkSource::new();
+        let (_, first) = source.deltas("wlan0", parse_station_dump(STATION_DUMP).unwrap());
+        assert_eq!(first.tx_retries, None);
+        assert_eq!(first.tx_bitrate_mbps, Some(433.3));
+
+        let later = STATION_DUMP
+            .replace("retries:\t120", "retries:\t150")
+            .replace("3600 seconds", "3660 seconds");
+        let (signal, second) = source.deltas("wlan0", parse_station_dump(&later).unwrap());
+        assert_eq!(signal, Some(-54));
+        assert_eq!(second.tx_retries, Some(30));
+        assert_eq!(second.tx_failed, Some(0));
+
+        // Reconnecting resets the counters
+        let reconnected = STATION_DUMP.replace("3600 seconds", "10 seconds");
+        let (_, third) = source.deltas("wlan0", parse_station_dump(&reconnected).unwrap());
+        assert_eq!(third.tx_retries, None);
+    }
+
+    #[test]
+    fn link_stats_are_kept_without_a_signal() {
+        let mut report = MonitorReport {
+            connection_used: Connection::SSID("MyWifi".into()),
+            ..MonitorReport::default()
+        };
+        let link = LinkStats {
+            tx_failed: Some(5),
+            ..LinkStats::default()
+        };
+        add_link_stats(&mut report, None, link.clone());
+        assert!(report.connections[0].stats.is_none());
+        assert_eq!(report.connections[0].link, Some(link));
+
+        add_link_stats(&mut report, Some(-60), LinkStats::default());
+        assert_eq!(report.connections.len(), 1);
+        assert_eq!(report.connections[0].stats.as_ref().unwrap().power_dbs, -60);
+    }
+}
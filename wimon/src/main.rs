@@ -1,16 +1,18 @@
 use std::{env, io};
 use std::path::PathBuf;
-use std::sync::mpsc::channel;
-use std::time::Duration;
+use std::process::Command;
 
-use service_manager::{
-    ServiceInstallCtx, ServiceLabel, ServiceManager, ServiceStartCtx, ServiceStopCtx,
-    ServiceUninstallCtx,
-};
+use service_manager::ServiceLabel;
 
-use config::MonitorSpec;
+use config::{Config, ConfigError, Identity, LayeredConfig, MonitorSpec, RemoteSettings};
+use wimon::remote::RemoteConfig;
+use wimon::{device_id, history, Monitor};
 
-mod monitor;
+mod configure;
+mod service;
+mod status;
+#[cfg(test)]
+mod test_util;
 
 const CONFIG_FILE_NAME: &str = "monitor.toml";
 
@@ -19,112 +21,205 @@ const SERVICE_NAME: &str = "net.mackenzie-serres.pingr.wimon";
 fn main() -> Result<(), io::Error> {
     let service_name: ServiceLabel = SERVICE_NAME.parse().unwrap();
 
-    let args: Vec<_> = env::args().collect();
-    match args.get(1).map(|s| s.as_str()) {
-        None => {
-            let config_file_path = config::find_config_file(CONFIG_FILE_NAME)?;
-            run(&config_file_path)?;
-        }
-        Some("install") => install_service(&service_name, &args[0])?,
-        Some("uninstall") => uninstall_service(&service_name)?,
-        _ => eprintln!("Invalid argument(s): '{}'", &args[1..].join(", ")),
+    let mut args: Vec<_> = env::args().collect();
+    let as_json = take_flag(&mut args, "--json");
+    let config_file_path = match take_option(&mut args, "--config")
+        .or_else(|| env::var(config::CONFIG_FILE_ENV).ok())
+    {
+        Some(path) => Some(PathBuf::from(path).canonicalize()?),
+        None => config::find_config_file(CONFIG_FILE_NAME).ok(),
+    };
+    let mut overrides = vec![];
+    while let Some(setting_value) = take_option(&mut args, "--set") {
+        overrides.push(setting_value);
     }
 
-    Ok(())
-}
-
-fn run(config_file_path: &PathBuf) -> Result<(), io::Error> {
-    let config = config::read_config(config_file_path)?;
-    println!(
-        "Config file loaded from: \"{}\"",
-        config_file_path.display()
-    );
-    println!(
-        "Monitor: {:?}",
-        config.monitor.as_ref().unwrap_or(&MonitorSpec::Connection)
-    );
-
-    let (tx, rx) = channel();
-    ctrlc::set_handler(move || {
-        println!("Control-C captured, sending Stop report");
-        tx.send(()).expect("Could not send signal on channel.")
-    })
-        .expect("Error setting Ctrl-C handler");
+    let config_file = || config_file_path.clone().ok_or_else(config_not_found);
+    // The config file is optional when all the settings needed are given as overrides
+    let load_config = {
+        let (path, overrides) = (config_file_path.clone(), overrides.clone());
+        move |remote: Option<&RemoteSettings>| {
+            config::load_config_as(path.as_ref(), &overrides, identify, remote)
+        }
+    };
 
-    monitor::monitor_loop(config, rx)?;
+    let command = args.get(1).cloned();
+    // The history command takes its own options below, any other options are a mistake
+    if command.as_deref() != Some("history") {
+        reject_options(&args)?;
+    }
 
-    println!("Exiting");
+    match command.as_deref() {
+        None => run(config_file_path.as_ref(), load_config)?,
+        Some("install") => service::install_service(&service_name, &args[0], &config_file()?)?,
+        Some("uninstall") => service::uninstall_service(&service_name, config_file_path.as_ref())?,
+        Some("status") => status::print_status(&service_name, &config_file()?, as_json)?,
+        Some("device-id") => {
+            let config = load_config(None)?.config;
+            match args.get(2).map(|s| s.as_str()) {
+                None => device_id::print_device_id(&config)?,
+                Some("rotate") => device_id::rotate_device_id(&config)?,
+                _ => eprintln!("Invalid argument(s): '{}'", &args[1..].join(", ")),
+            }
+        }
+        Some("history") => {
+            let (from, to) = (take_option(&mut args, "--from"), take_option(&mut args, "--to"));
+            let connection = take_option(&mut args, "--connection");
+            let format = take_option(&mut args, "--format").unwrap_or("text".into());
+            reject_options(&args)?;
+            let history_filter = history::HistoryFilter {
+                from: from.map(|time| history::parse_time(&time)).transpose()?,
+                to: to.map(|time| history::parse_time(&time)).transpose()?,
+                connection,
+            };
+            let config = load_config(None)?.config;
+            history::print_history(&config, &history_filter, &format)?
+        }
+        Some("config") => match args.get(2).map(|s| s.as_str()) {
+            Some("show") => {
+                let (layered, _) = load_with_remote(&load_config)?;
+                println!("# Device: {:?}", layered.identity);
+                println!("{}", layered.describe());
+            }
+            Some("init") => {
+                let path = args.get(3).map_or(PathBuf::from(CONFIG_FILE_NAME), PathBuf::from);
+                configure::init_config(&path, io::stdin().lock(), io::stdout())?
+            }
+            Some("check") => {
+                let path = args.get(3).map(PathBuf::from).or(config_file_path);
+                configure::check_config(path.as_ref(), || {
+                    config::load_config_as(path.as_ref(), &overrides, identify, None)
+                })?
+            }
+            _ => eprintln!("Invalid argument(s): '{}'", &args[1..].join(", ")),
+        },
+        _ => eprintln!("Invalid argument(s): '{}'", &args[1..].join(", ")),
+    }
 
     Ok(())
 }
 
-fn get_service_manager() -> Result<Box<dyn ServiceManager>, io::Error> {
-    // Get generic service by detecting what is available on the platform
-    let manager = <dyn ServiceManager>::native()
-        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Could not create ServiceManager"))?;
-
-    Ok(manager)
+// Remove an option and the value following it from the args, returning the value if found
+fn take_option(args: &mut Vec<String>, option: &str) -> Option<String> {
+    let position = args.iter().position(|arg| arg == option)?;
+    args.remove(position);
+    if position < args.len() {
+        Some(args.remove(position))
+    } else {
+        None
+    }
 }
 
-// This will install the binary as a user level service and then start it
-fn install_service(service_name: &ServiceLabel, path_to_exec: &str) -> Result<(), io::Error> {
-    let manager = get_service_manager()?;
-    let exec_path = PathBuf::from(path_to_exec).canonicalize()?;
-    // Run from dir where exec is for now, so it should find the config file in ancestors path
-    let exec_dir = exec_path
-        .parent()
-        .ok_or(io::Error::new(
-            io::ErrorKind::NotFound,
-            "Could not get exec dir",
-        ))?
-        .to_path_buf();
-
-    // Install our service using the underlying service management platform
-    manager.install(ServiceInstallCtx {
-        label: service_name.clone(),
-        program: exec_path,
-        args: vec![],
-        contents: None, // Optional String for system-specific service content.
-        username: None, // Optional String for alternative user to run service.
-        working_directory: Some(exec_dir),
-        environment: None, // Optional list of environment variables to supply the service process.
-        autostart: true, // autostart on reboot
-    })?;
-
-    // Start our service using the underlying service management platform
-    manager.start(ServiceStartCtx {
-        label: service_name.clone(),
-    })?;
+// Remove a flag from the args, returning true if it was present
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let length = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != length
+}
 
-    println!(
-        "'service '{}' ('{}') installed and started",
-        service_name, path_to_exec
-    );
+// Return an error for the first option left in the args, once the known ones have been taken
+fn reject_options(args: &[String]) -> Result<(), io::Error> {
+    match args.iter().skip(1).find(|arg| arg.starts_with("--")) {
+        Some(option) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Unknown option '{option}' for '{}'",
+                args.get(1)
+                    .filter(|command| !command.starts_with("--"))
+                    .map_or("wimon", |command| command.as_str())
+            ),
+        )),
+        None => Ok(()),
+    }
+}
 
-    Ok(())
+fn config_not_found() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!(
+            "No config file specified with '--config' or {}, and '{CONFIG_FILE_NAME}' not found",
+            config::CONFIG_FILE_ENV
+        ),
+    )
 }
 
-// this will stop any running instance of the service, then uninstall it
-fn uninstall_service(service_name: &ServiceLabel) -> Result<(), io::Error> {
-    let manager = get_service_manager()?;
+// The identity of this device, for choosing the overrides that apply to it in a config file
+// shared by a fleet of devices
+fn identify(config: &Config) -> Identity {
+    let hostname = Command::new("hostname")
+        .output()
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|hostname| !hostname.is_empty());
+    Identity {
+        device_id: device_id::find_device_id(config).ok().flatten(),
+        hostname,
+        ..Identity::of(config)
+    }
+}
 
-    // Stop our service using the underlying service management platform
-    manager.stop(ServiceStopCtx {
-        label: service_name.clone(),
-    })?;
+// Load the config with the remote settings from the collector, if they are enabled. If they
+// can't be fetched, or are not valid, the local config is used
+fn load_with_remote(
+    load: &impl Fn(Option<&RemoteSettings>) -> Result<LayeredConfig, ConfigError>,
+) -> Result<(LayeredConfig, Option<RemoteConfig>), io::Error> {
+    let local = load(None)?;
+    let device_id = device_id::get_device_id(&local.config)?;
+    let Some(mut remote) = RemoteConfig::new(&local.config, &device_id) else {
+        return Ok((local, None));
+    };
+    let settings = match remote.fetch() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Using the local config: {e}");
+            return Ok((local, Some(remote)));
+        }
+    };
+    match load(settings.as_ref()) {
+        Ok(layered) => Ok((layered, Some(remote))),
+        Err(e) => {
+            eprintln!("Using the local config, as the remote config is not valid: {e}");
+            Ok((local, Some(remote)))
+        }
+    }
+}
 
+fn run(
+    config_file_path: Option<&PathBuf>,
+    load: impl Fn(Option<&RemoteSettings>) -> Result<LayeredConfig, ConfigError> + Send + 'static,
+) -> Result<(), io::Error> {
+    let (layered, remote) = load_with_remote(&load)?;
+    let config = layered.config;
+    match config_file_path {
+        Some(path) => println!("Config file loaded from: \"{}\"", path.display()),
+        None => println!("No config file found, using defaults and overrides"),
+    }
     println!(
-        "service '{}' stopped. Waiting for 10s before uninstalling",
-        service_name
+        "Monitor: {:?}",
+        config.monitor.as_ref().unwrap_or(&MonitorSpec::Connection)
     );
-    std::thread::sleep(Duration::from_secs(10));
 
-    // Uninstall our service using the underlying service management platform
-    manager.uninstall(ServiceUninstallCtx {
-        label: service_name.clone(),
-    })?;
+    let handle = Monitor::builder(config).watch_sleep(true).build()?.start()?;
+    if let Some(remote) = remote {
+        println!(
+            "Checking for changes to the remote config at '{}' every {}s",
+            remote.url(),
+            remote.poll_period().as_secs()
+        );
+        remote.watch(handle.reconfigurer(), move |settings| {
+            Ok(load(settings)?.config)
+        })?;
+    }
+    let stopper = handle.stopper();
+    ctrlc::set_handler(move || {
+        println!("Control-C captured, sending Stop report");
+        stopper.stop()
+    })
+        .expect("Error setting Ctrl-C handler");
 
-    println!("service '{}' uninstalled", service_name);
+    handle.wait()?;
+
+    println!("Exiting");
 
     Ok(())
 }
@@ -135,7 +230,7 @@ mod test {
 
     use config::{Config, MonitorSpec};
 
-    use super::CONFIG_FILE_NAME;
+    use super::{reject_options, take_option, CONFIG_FILE_NAME};
 
     #[test]
     fn bundled_spec() {
@@ -149,4 +244,21 @@ mod test {
         assert_eq!(config.monitor, Some(MonitorSpec::Connection));
         assert_eq!(config.report.unwrap().period_seconds, Some(60));
     }
+
+    #[test]
+    fn history_options_are_rejected_for_other_commands() {
+        let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+
+        let error = reject_options(&args("wimon install --from 2024-01-01")).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown option '--from' for 'install'");
+        let error = reject_options(&args("wimon --format json")).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown option '--format' for 'wimon'");
+        assert!(reject_options(&args("wimon config show")).is_ok());
+
+        let mut history = args("wimon history --from 2024-01-01 --format json");
+        assert_eq!(take_option(&mut history, "--from"), Some("2024-01-01".into()));
+        assert!(reject_options(&history).is_err());
+        assert_eq!(take_option(&mut history, "--format"), Some("json".into()));
+        assert!(reject_options(&history).is_ok());
+    }
 }
@@ -1,221 +1,696 @@
-use config::Config;
-#[cfg(feature = "ssids")]
-use config::MonitorSpec;
-use curl::easy::Easy;
-use data_model::{Connection, DeviceId, MonitorReport, ReportType};
-#[cfg(feature = "ssids")]
-use data_model::{ConnectionReport, Stats};
-use machineid_rs::{Encryption, HWIDComponent, IdBuilder};
-use serde_json::json;
 use std::io;
-use std::io::Read;
-use std::process::Command;
-use std::sync::mpsc::Receiver;
-#[cfg(feature = "ssids")]
-use wifiscanner::Wifi;
-
-pub(crate) fn monitor_loop(config: Config, term_receiver: Receiver<()>) -> Result<(), io::Error> {
-    let device_id = get_device_id()?;
-    println!("Device ID = {device_id}");
-
-    // A "sleep", interruptible by receiving a message to exit. Normal looping will produce
-    // a timeout error, in which case send the periodic report.
-    while term_receiver.recv_timeout(config.period_duration).is_err() {
-        // Avoid failing on one error
-        let _ = send_report(&config, &device_id, ReportType::OnGoing, &measure(&config)?);
-    }
-
-    // Tell the server that this device is stopping sending of reports
-    send_report(&config, &device_id, ReportType::Stop, &measure(&config)?)
-}
-
-#[cfg(feature = "ssids")]
-fn add_report(report: &mut MonitorReport, wifi: &Wifi) {
-    report.connections.push(ConnectionReport {
-        connection: Connection::SSID(wifi.ssid.clone()),
-        stats: Some(Stats {
-            power_dbs: wifi.signal_level.parse::<i16>().unwrap_or(0),
-        }),
-    });
-}
-#[cfg_attr(not(feature = "ssids"), allow(unused_variables))]
-fn measure(config: &Config) -> Result<MonitorReport, io::Error> {
-    let ssid = get_ssid().map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Could not get SSID: '{e}'"),
-        )
-    })?;
-
-    #[cfg_attr(not(feature = "ssids"), allow(unused_mut))]
-    let mut report = MonitorReport {
-        connection_used: Connection::SSID(ssid.clone()),
-        connections: vec![],
-    };
-
-    #[cfg(feature = "ssids")]
-    match &config.monitor.as_ref().unwrap_or(&MonitorSpec::Connection) {
-        MonitorSpec::All => {
-            let wifis = wifiscanner::scan().unwrap_or_default();
-            for wifi in wifis {
-                add_report(&mut report, &wifi);
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use config::Config;
+use data_model::{DeviceId, MonitorReport, ReportType};
+
+use crate::adaptive::AdaptivePeriod;
+use crate::addressing::AddressingSource;
+use crate::collector::CollectorSink;
+use crate::device_id::get_device_id;
+use crate::history::History;
+use crate::hooks::Hooks;
+use crate::interfaces::InterfaceSource;
+#[cfg(target_os = "linux")]
+use crate::link::LinkSource;
+use crate::power::{ClockCheck, SleepWatcher};
+use crate::probe::ProbeSource;
+use crate::sampling::Sampler;
+use crate::schedule::Schedule;
+use crate::simulation::Simulation;
+use crate::state::{now, state_file_path, MonitorState};
+use crate::trace::TraceSource;
+use crate::wifi::WifiSource;
+
+// Reasons given in reports sent due to the system suspending or resuming
+const SUSPEND_REASON: &str = "suspend";
+const RESUME_REASON: &str = "resume";
+// Reason given in the report sent after the config has changed
+const RECONFIGURE_REASON: &str = "config";
+
+/// Events that the monitor loop reacts to, in addition to the report period expiring
+#[derive(Debug)]
+pub(crate) enum MonitorEvent {
+    /// Stop monitoring, after sending a Stop report
+    Terminate,
+    /// The system is about to suspend
+    Suspending,
+    /// The system has resumed after being suspended
+    Resumed,
+    /// Use a new config, e.g. one fetched from the collector
+    Reconfigure(Box<Config>),
+}
+
+/// A [Source] of measurements adds what it measures to the report being built.
+/// The default sources are [WifiSource], [ProbeSource], `LinkSource` (Linux only) and, if
+/// configured, [InterfaceSource], [TraceSource] and [AddressingSource]. They are replaced by
+/// the replay of a timeline if a simulation is configured.
+pub trait Source: Send {
+    fn measure(&mut self, report: &mut MonitorReport) -> Result<(), io::Error>;
+}
+
+/// A [Sink] is sent each report made by the monitor.
+/// The default sink is [CollectorSink].
+pub trait Sink: Send {
+    fn send(&mut self, report: &Report) -> Result<(), io::Error>;
+}
+
+/// A report to be sent to the sinks
+#[derive(Debug)]
+pub struct Report<'a> {
+    pub device_id: &'a DeviceId,
+    pub report_type: &'a ReportType,
+    /// Why the report was sent outside the normal period, e.g. "suspend"
+    pub reason: Option<&'a str>,
+    /// How long until the next report is due
+    pub period: Duration,
+    pub report: &'a MonitorReport,
+}
+
+/// Builder of a [Monitor], created using [Monitor::builder]
+pub struct MonitorBuilder {
+    config: Config,
+    device_id: Option<DeviceId>,
+    sources: Vec<Box<dyn Source>>,
+    sinks: Vec<Box<dyn Sink>>,
+    default_sources: bool,
+    default_sinks: bool,
+    watch_sleep: bool,
+}
+
+impl MonitorBuilder {
+    /// Use this device ID, instead of the one determined from the config
+    pub fn device_id(mut self, device_id: &str) -> Self {
+        self.device_id = Some(device_id.to_string());
+        self
+    }
+
+    /// Add a source of measurements, after the default ones
+    pub fn source(mut self, source: impl Source + 'static) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// Add a sink to send reports to, after the default one
+    pub fn sink(mut self, sink: impl Sink + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Don't use the default sources ([WifiSource], [ProbeSource], `LinkSource`,
+    /// [InterfaceSource], [TraceSource] and [AddressingSource])
+    pub fn without_default_sources(mut self) -> Self {
+        self.default_sources = false;
+        self
+    }
+
+    /// Don't use the default sink ([CollectorSink])
+    pub fn without_default_sinks(mut self) -> Self {
+        self.default_sinks = false;
+        self
+    }
+
+    /// Watch for the system suspending and resuming while running (Linux only), sending a Stop
+    /// report before it suspends. Default: false
+    pub fn watch_sleep(mut self, watch_sleep: bool) -> Self {
+        self.watch_sleep = watch_sleep;
+        self
+    }
+
+    pub fn build(self) -> Result<Monitor, io::Error> {
+        let device_id = match self.device_id {
+            Some(device_id) => device_id,
+            None => get_device_id(&self.config)?,
+        };
+
+        let defaults = Defaults::new(&self.config, self.default_sources, self.default_sinks)?;
+        let (default_sources, default_sinks) = (defaults.sources.len(), defaults.sinks.len());
+        let mut sources = defaults.sources;
+        sources.extend(self.sources);
+        let mut sinks = defaults.sinks;
+        sinks.extend(self.sinks);
+
+        Ok(Monitor {
+            config: self.config,
+            device_id,
+            sources,
+            sinks,
+            default_sources,
+            default_sinks,
+            watch_sleep: self.watch_sleep,
+            simulated: defaults.simulated,
+        })
+    }
+}
+
+/// The default sources and sinks for a config
+struct Defaults {
+    sources: Vec<Box<dyn Source>>,
+    sinks: Vec<Box<dyn Sink>>,
+    simulated: bool,
+}
+
+impl Defaults {
+    fn new(config: &Config, with_sources: bool, with_sinks: bool) -> Result<Self, io::Error> {
+        // A simulation replaces the default sources, and makes the default sink fail in outages
+        let simulation = config
+            .simulation
+            .as_ref()
+            .map(|spec| Simulation::load(Path::new(&spec.timeline_file)))
+            .transpose()?;
+
+        let mut sources: Vec<Box<dyn Source>> = vec![];
+        if with_sources {
+            match &simulation {
+                Some(simulation) => sources.push(Box::new(simulation.source())),
+                None => sources.extend(default_sources(config)),
             }
         }
-        MonitorSpec::Connection => {
-            let wifis = wifiscanner::scan().unwrap_or_default();
-            for wifi in wifis {
-                if wifi.ssid == ssid {
-                    add_report(&mut report, &wifi);
-                }
+
+        let mut sinks: Vec<Box<dyn Sink>> = vec![];
+        if with_sinks {
+            let collector = CollectorSink::new(config);
+            match &simulation {
+                Some(simulation) => sinks.push(Box::new(simulation.sink(collector))),
+                None => sinks.push(Box::new(collector)),
             }
         }
-    };
 
-    Ok(report)
+        Ok(Defaults {
+            sources,
+            sinks,
+            simulated: simulation.is_some(),
+        })
+    }
 }
 
-fn send_report(
-    config: &Config,
-    device_id: &DeviceId,
-    report_type: ReportType,
-    report: &MonitorReport,
-) -> Result<(), io::Error> {
-    let report_url = config.report_url.as_ref().map(|p| {
-        p.join(&format!(
-            "report/{}?device_id={}&connection={}&period={}",
-            report_type.to_string().to_ascii_lowercase(),
-            device_id,
-            report.connection_used,
-            config.period_duration.as_secs()
-        ))
-        .unwrap()
-    });
-
-    let mut data = Vec::new();
-    if let Some(url) = &report_url {
-        let json_string = format!("report={}", json!(report));
-        let mut post_data = json_string.as_bytes();
-        let mut easy = Easy::new();
-        let result;
-        easy.url(url.as_str()).map_err(|_| {
-            io::Error::new(io::ErrorKind::NotFound, "Could not set url on curl request")
-        })?;
-        {
-            easy.post(true).map_err(|_| {
-                io::Error::new(
-                    io::ErrorKind::NotFound,
-                    "Could not set POST on curl request",
-                )
-            })?;
-            easy.post_fields_copy(post_data).map_err(|_| {
-                io::Error::new(
-                    io::ErrorKind::NotFound,
-                    "Could not add POST data on curl request",
-                )
-            })?;
-            easy.post_field_size(post_data.len() as u64).map_err(|_| {
-                io::Error::new(
-                    io::ErrorKind::NotFound,
-                    "Could not set POST field size on curl request",
-                )
-            })?;
-            let mut transfer = easy.transfer();
-            transfer
-                .read_function(|buf| Ok(post_data.read(buf).unwrap()))
-                .map_err(|_| {
-                    io::Error::new(
-                        io::ErrorKind::NotFound,
-                        "Could not read data for curl request",
-                    )
-                })?;
-            transfer.write_function(|new_data| {
-                data.extend_from_slice(new_data);
-                Ok(new_data.len())
-            })?;
-            result = transfer.perform();
-        }
-        match result {
-            Ok(_) => {
-                println!("Sent {} report to: {}", report_type, url.host().unwrap());
-                println!("Response: {}", String::from_utf8_lossy(&data));
+// The sources that measure the real network
+fn default_sources(config: &Config) -> Vec<Box<dyn Source>> {
+    let mut sources: Vec<Box<dyn Source>> = vec![
+        Box::new(WifiSource::new(config)),
+        #[cfg(target_os = "linux")]
+        Box::new(LinkSource::new()),
+        Box::new(ProbeSource::new(config)),
+    ];
+    if let Some(interfaces) = InterfaceSource::new(config) {
+        sources.push(Box::new(interfaces));
+    }
+    if let Some(trace) = TraceSource::new(config) {
+        sources.push(Box::new(trace));
+    }
+    if let Some(addressing) = AddressingSource::new(config) {
+        sources.push(Box::new(addressing));
+    }
+    sources
+}
+
+/// A [Monitor] takes measurements from its sources and sends them as reports to its sinks,
+/// either one-shot or periodically (in the background) after being started
+pub struct Monitor {
+    config: Config,
+    device_id: DeviceId,
+    sources: Vec<Box<dyn Source>>,
+    sinks: Vec<Box<dyn Sink>>,
+    /// The number of `sources` and `sinks` at the start that are the default ones, which are
+    /// made from the config
+    default_sources: usize,
+    default_sinks: usize,
+    watch_sleep: bool,
+    /// Measurements are simulated, so the real network should not be sampled
+    simulated: bool,
+}
+
+impl Monitor {
+    pub fn builder(config: Config) -> MonitorBuilder {
+        MonitorBuilder {
+            config,
+            device_id: None,
+            sources: vec![],
+            sinks: vec![],
+            default_sources: true,
+            default_sinks: true,
+            watch_sleep: false,
+        }
+    }
+
+    pub fn device_id(&self) -> &DeviceId {
+        &self.device_id
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Use a new config, replacing the default sources and sinks with ones made from it. The
+    /// device ID and the sources and sinks added to the builder are kept.
+    pub fn reconfigure(&mut self, config: Config) -> Result<(), io::Error> {
+        let defaults = Defaults::new(&config, self.default_sources > 0, self.default_sinks > 0)?;
+        let (default_sources, default_sinks) = (defaults.sources.len(), defaults.sinks.len());
+        self.sources
+            .splice(..self.default_sources, defaults.sources);
+        self.sinks.splice(..self.default_sinks, defaults.sinks);
+        (self.default_sources, self.default_sinks) = (default_sources, default_sinks);
+        self.simulated = defaults.simulated;
+        self.config = config;
+        Ok(())
+    }
+
+    /// Take a measurement from all the sources. A source that fails is logged and leaves its
+    /// part of the report empty, so the measurements of the others are still reported
+    pub fn measure(&mut self) -> MonitorReport {
+        let mut report = MonitorReport::default();
+        for source in self.sources.iter_mut() {
+            if let Err(e) = source.measure(&mut report) {
+                eprintln!("Could not measure: {e}");
+            }
+        }
+        report
+    }
+
+    /// Send a report to all the sinks, returning the last error if any of them failed
+    pub fn send(
+        &mut self,
+        report_type: &ReportType,
+        reason: Option<&str>,
+        report: &MonitorReport,
+    ) -> Result<(), io::Error> {
+        let period = self.config.period_duration;
+        self.send_with_period(report_type, reason, period, report)
+    }
+
+    fn send_with_period(
+        &mut self,
+        report_type: &ReportType,
+        reason: Option<&str>,
+        period: Duration,
+        report: &MonitorReport,
+    ) -> Result<(), io::Error> {
+        let report = Report {
+            device_id: &self.device_id,
+            report_type,
+            reason,
+            period,
+            report,
+        };
+        let mut result = Ok(());
+        for sink in self.sinks.iter_mut() {
+            if let Err(e) = sink.send(&report) {
+                result = Err(e);
             }
-            Err(_) => eprintln!("Error reporting to '{}': skipping report", url.as_str()),
         }
         result
-            .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Could not perform curl request"))
-    } else {
-        println!("Local Status: \n{report}");
-        Ok(())
+    }
+
+    /// Start monitoring in the background, sending a report every period until stopped,
+    /// when a Stop report is sent
+    pub fn start(self) -> Result<MonitorHandle, io::Error> {
+        let (sender, events) = channel();
+        let sleep_watcher = if self.watch_sleep {
+            SleepWatcher::start(sender.clone())
+                .map_err(|e| eprintln!("Not watching for system suspend: {e}"))
+                .ok()
+        } else {
+            None
+        };
+
+        let thread = thread::Builder::new()
+            .name("monitor".into())
+            .spawn(move || Running::new(self).run(events, sleep_watcher))?;
+
+        Ok(MonitorHandle {
+            stopper: Stopper(sender),
+            thread,
+        })
     }
 }
 
-fn get_device_id() -> Result<DeviceId, io::Error> {
-    let mut builder = IdBuilder::new(Encryption::SHA256);
-    builder
-        .add_component(HWIDComponent::CPUID)
-        .add_component(HWIDComponent::SystemID);
-    builder
-        .build("device_id")
-        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Could not build unique device_id"))
+/// A handle on a started [Monitor]
+pub struct MonitorHandle {
+    stopper: Stopper,
+    thread: JoinHandle<Result<(), io::Error>>,
 }
 
-#[cfg(target_os = "macos")]
-fn get_ssid() -> Result<String, io::Error> {
-    let output = Command::new("/usr/sbin/networksetup")
-        .arg("-getairportnetwork")
-        .arg("en0")
-        .output()
-        .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))?;
+impl MonitorHandle {
+    /// Get a [Stopper] that can be used to stop the monitor, e.g. from a signal handler
+    pub fn stopper(&self) -> Stopper {
+        self.stopper.clone()
+    }
+
+    /// Get a [Reconfigurer] that can be used to give the monitor a new config
+    pub fn reconfigurer(&self) -> Reconfigurer {
+        Reconfigurer(self.stopper.0.clone())
+    }
+
+    /// Stop the monitor, waiting for it to send its Stop report
+    pub fn stop(self) -> Result<(), io::Error> {
+        self.stopper.stop();
+        self.wait()
+    }
+
+    /// Wait for the monitor to stop, returning the outcome of its Stop report
+    pub fn wait(self) -> Result<(), io::Error> {
+        self.thread
+            .join()
+            .map_err(|_| io::Error::other("Monitor thread panicked"))?
+    }
+}
+
+/// Used to ask a started [Monitor] to stop
+#[derive(Clone)]
+pub struct Stopper(Sender<MonitorEvent>);
 
-    if !output.status.success() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("{}", String::from_utf8_lossy(&output.stderr)),
-        ));
+impl Stopper {
+    pub fn stop(&self) {
+        let _ = self.0.send(MonitorEvent::Terminate);
     }
+}
 
-    let data = String::from_utf8_lossy(&output.stdout);
+/// Used to give a started [Monitor] a new config, which it uses from its next report
+#[derive(Clone)]
+pub struct Reconfigurer(Sender<MonitorEvent>);
 
-    parse_ssid(&data)
+impl Reconfigurer {
+    /// Send the monitor a new config, returning false if it has stopped
+    pub fn reconfigure(&self, config: Config) -> bool {
+        self.0
+            .send(MonitorEvent::Reconfigure(Box::new(config)))
+            .is_ok()
+    }
 }
 
-#[cfg(target_os = "macos")]
-fn parse_ssid(data: &str) -> Result<String, io::Error> {
-    for line in data.lines() {
-        let mut pair = line.trim().split(':');
-        if pair.next().unwrap() == "Current Wi-Fi Network" {
-            return Ok(pair.next().unwrap().trim().to_owned());
+/// The state of a running monitor
+struct Running {
+    monitor: Monitor,
+    state: MonitorState,
+    history: Option<History>,
+    schedule: Schedule,
+    sampler: Option<Sampler>,
+    hooks: Option<Hooks>,
+    /// The current report period, which only changes if an adaptive period is configured, or
+    /// the config changes
+    period: Duration,
+    adaptive: Option<AdaptivePeriod>,
+}
+
+impl Running {
+    fn new(monitor: Monitor) -> Self {
+        let config = &monitor.config;
+        let device_id = &monitor.device_id;
+        println!("Device ID = {device_id}");
+
+        let period = config.period_duration;
+        Running {
+            state: MonitorState::new(device_id),
+            schedule: Schedule::start(config, device_id, period),
+            sampler: Sampler::new(config).filter(|_| !monitor.simulated),
+            hooks: Hooks::new(config, device_id),
+            history: open_history(config),
+            period,
+            adaptive: adaptive_period(config),
+            monitor,
+        }
+    }
+
+    // Use a new config, remaking everything that is made from it. If it can't be used (e.g. the
+    // timeline of a simulation can't be read) the current config is kept
+    fn reconfigure(&mut self, config: Config) -> bool {
+        if let Err(e) = self.monitor.reconfigure(config) {
+            eprintln!("Could not use the new config, keeping the current one: {e}");
+            return false;
+        }
+        println!("Using the new config");
+        let config = &self.monitor.config;
+        self.sampler = Sampler::new(config).filter(|_| !self.monitor.simulated);
+        self.hooks = Hooks::new(config, &self.monitor.device_id);
+        self.history = open_history(config);
+        self.period = config.period_duration;
+        self.adaptive = adaptive_period(config);
+        true
+    }
+
+    fn run(
+        mut self,
+        events: Receiver<MonitorEvent>,
+        mut sleep_watcher: Option<SleepWatcher>,
+    ) -> Result<(), io::Error> {
+        self.state.scheduled(Some(self.schedule.wait()));
+        self.save_state();
+
+        let mut clock_check = ClockCheck::new();
+        loop {
+            // Wait until the next report, or sample, is due
+            let wait = match &self.sampler {
+                Some(sampler) => sampler.wait().min(self.schedule.wait()),
+                None => self.schedule.wait(),
+            };
+            // A "sleep", interruptible by receiving an event. Normal looping will produce
+            // a timeout error, in which case send the periodic report.
+            match events.recv_timeout(wait) {
+                Err(RecvTimeoutError::Timeout) if !self.schedule.wait().is_zero() => {
+                    if let Some(sampler) = self.sampler.as_mut() {
+                        sampler.sample();
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    // If we were suspended without being notified, report that we have resumed
+                    let reason = clock_check.suspended().map(|duration| {
+                        println!("System was suspended for {}s", duration.as_secs());
+                        RESUME_REASON
+                    });
+                    let mut report = self.monitor.measure();
+                    if let Some(sampler) = self.sampler.as_mut() {
+                        sampler.finish(&mut report);
+                    }
+                    // Avoid failing on one error
+                    let _ = self.send_and_record(ReportType::OnGoing, reason, report);
+
+                    let missed = self.schedule.advance(self.period);
+                    if missed > 0 {
+                        eprintln!("Missed {missed} scheduled report(s)");
+                        self.state.missed(missed);
+                    }
+                }
+                Ok(MonitorEvent::Suspending) => {
+                    // Tell the server this device is stopping, so it is not considered Offline
+                    println!("System is suspending, sending Stop report");
+                    self.report_now(ReportType::Stop, SUSPEND_REASON);
+                    self.state.scheduled(None);
+                    self.save_state();
+                    if let Some(watcher) = sleep_watcher.as_mut() {
+                        watcher.release();
+                    }
+
+                    // Wait until the system resumes, or we are asked to terminate
+                    loop {
+                        match events.recv() {
+                            Ok(MonitorEvent::Resumed) => break,
+                            Ok(MonitorEvent::Suspending) => {}
+                            Ok(MonitorEvent::Reconfigure(config)) => {
+                                self.reconfigure(*config);
+                            }
+                            Ok(MonitorEvent::Terminate) | Err(_) => return Ok(()),
+                        }
+                    }
+                    self.resumed(&mut sleep_watcher);
+                }
+                Ok(MonitorEvent::Resumed) => self.resumed(&mut sleep_watcher),
+                Ok(MonitorEvent::Reconfigure(config)) => {
+                    // Report straight away, so the server knows the period until the next one
+                    if self.reconfigure(*config) {
+                        self.report_now(ReportType::OnGoing, RECONFIGURE_REASON);
+                        self.schedule.restart(self.monitor.config(), self.period);
+                    }
+                }
+                Ok(MonitorEvent::Terminate) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            self.state.scheduled(Some(self.schedule.wait()));
+            self.save_state();
+            clock_check = ClockCheck::new();
+        }
+
+        // Tell the server that this device is stopping sending of reports
+        let report = self.monitor.measure();
+        let result = self.send_and_record(ReportType::Stop, None, report);
+        self.state.scheduled(None);
+        self.save_state();
+        result
+    }
+
+    // After resuming, take the inhibitor lock again and send a report straight away so the
+    // server knows this device is reporting again
+    fn resumed(&mut self, sleep_watcher: &mut Option<SleepWatcher>) {
+        println!("System resumed, sending report");
+        if let Some(watcher) = sleep_watcher.as_mut() {
+            watcher.acquire();
+        }
+        self.report_now(ReportType::OnGoing, RESUME_REASON);
+        self.schedule.restart(self.monitor.config(), self.period);
+    }
+
+    // Measure and send a report outside the normal period. The network may not be available
+    // around suspend and resume, so failures are logged but not fatal
+    fn report_now(&mut self, report_type: ReportType, reason: &str) {
+        let report = self.monitor.measure();
+        let _ = self.send_and_record(report_type, Some(reason), report);
+    }
+
+    // Send a measurement as a report of type `report_type`, recording it and the outcome in the
+    // monitor's state and history
+    fn send_and_record(
+        &mut self,
+        report_type: ReportType,
+        reason: Option<&str>,
+        report: MonitorReport,
+    ) -> Result<(), io::Error> {
+        // Decide the period until the next report first, so the server knows when to expect it
+        if let Some(adaptive) = self.adaptive.as_mut() {
+            self.period = adaptive.update(adaptive.degraded(&report));
+        }
+        let result = self
+            .monitor
+            .send_with_period(&report_type, reason, self.period, &report);
+        // The period was sent with the report, so a failure only shortens the next one
+        if let (Err(_), Some(adaptive)) = (&result, self.adaptive.as_mut()) {
+            adaptive.send_failed();
+        }
+        if let Some(hooks) = self.hooks.as_mut() {
+            hooks.after_report(&report, &result);
+        }
+        if let Some(history) = &self.history {
+            if let Err(e) = history.record(now(), &report_type, result.is_ok(), &report) {
+                eprintln!("Could not record measurement in history: {e}");
+            }
+        }
+        self.state.measured(report);
+        self.state.sent(&report_type, &result);
+        result
+    }
+
+    // Failing to save the state should not stop the monitor from reporting
+    fn save_state(&self) {
+        let data_path = &self.monitor.config.data_path;
+        if let Err(e) = self.state.save(data_path) {
+            eprintln!(
+                "Could not save state to '{}': {e}",
+                state_file_path(data_path).display()
+            );
         }
     }
+}
 
-    Err(io::Error::new(
-        io::ErrorKind::NotFound,
-        format!("Could not parse SSID name: '{data}'"),
-    ))
+// Failing to open the history should not stop the monitor from reporting
+fn open_history(config: &Config) -> Option<History> {
+    History::open(config)
+        .map_err(|e| eprintln!("Could not open measurement history: {e}"))
+        .ok()
+        .flatten()
 }
 
-// This will need improving for the case when there are multiple interfaces
-#[cfg(target_os = "linux")]
-fn get_ssid() -> Result<String, io::Error> {
-    let output = Command::new("iw")
-        .arg("dev")
-        .output()
-        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Could not execute 'iw'"))?;
-    let data = String::from_utf8_lossy(&output.stdout);
-    parse_ssid(&data)
+fn adaptive_period(config: &Config) -> Option<AdaptivePeriod> {
+    config
+        .report
+        .as_ref()
+        .and_then(|report| report.adaptive.as_ref())
+        .map(|spec| AdaptivePeriod::new(spec, config.period_duration))
 }
 
-#[cfg(target_os = "linux")]
-fn parse_ssid(data: &str) -> Result<String, io::Error> {
-    for line in data.lines() {
-        let mut pair = line.trim().split(' ');
-        if pair.next().unwrap() == "ssid" {
-            return Ok(pair.next().unwrap().trim().to_owned());
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use config::{Config, HistorySpec};
+    use data_model::{Connection, MonitorReport};
+
+    use super::{Monitor, Report, Sink, Source};
+    use crate::test_util::test_dir;
+
+    struct FixedSource;
+
+    impl Source for FixedSource {
+        fn measure(&mut self, report: &mut MonitorReport) -> Result<(), io::Error> {
+            report.connection_used = Connection::SSID("MyWifi".into());
+            Ok(())
+        }
+    }
+
+    struct FailingSource;
+
+    impl Source for FailingSource {
+        fn measure(&mut self, _report: &mut MonitorReport) -> Result<(), io::Error> {
+            Err(io::Error::other("no measurement"))
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct RecordingSink(Arc<Mutex<Vec<String>>>);
+
+    impl Sink for RecordingSink {
+        fn send(&mut self, report: &Report) -> Result<(), io::Error> {
+            self.0.lock().unwrap().push(format!(
+                "{}{}",
+                report.report_type, report.report.connection_used
+            ));
+            Ok(())
         }
     }
 
-    Err(io::Error::new(
-        io::ErrorKind::NotFound,
-        "Could not parse SSID name",
-    ))
+    fn config(name: &str) -> Config {
+        Config {
+            history: Some(HistorySpec {
+                enabled: Some(false),
+                retention_days: None,
+            }),
+            period_duration: Duration::from_millis(50),
+            data_path: test_dir(name),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn measure_once() {
+        let mut monitor = Monitor::builder(config("measure"))
+            .device_id("device")
+            .without_default_sources()
+            .source(FailingSource)
+            .source(FixedSource)
+            .build()
+            .unwrap();
+        // One source failing doesn't stop the others measuring
+        let report = monitor.measure();
+        assert_eq!(report.connection_used.to_string(), "\tssid=MyWifi");
+    }
+
+    #[test]
+    fn start_and_stop() {
+        let config = config("start");
+        let data_path = config.data_path.clone();
+        let sink = RecordingSink::default();
+        let monitor = Monitor::builder(config)
+            .device_id("device")
+            .without_default_sources()
+            .source(FixedSource)
+            .without_default_sinks()
+            .sink(sink.clone())
+            .build()
+            .unwrap();
+
+        let handle = monitor.start().unwrap();
+        std::thread::sleep(Duration::from_millis(175));
+        handle.stop().unwrap();
+
+        let sent = sink.0.lock().unwrap();
+        assert!(sent.len() >= 3);
+        assert!(sent[..sent.len() - 1]
+            .iter()
+            .all(|s| s == "OnGoing\tssid=MyWifi"));
+        assert_eq!(sent.last().unwrap(), "Stop\tssid=MyWifi");
+        std::fs::remove_dir_all(&data_path).unwrap();
+    }
 }
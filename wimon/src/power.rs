@@ -0,0 +1,210 @@
+use std::io;
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(target_os = "linux")]
+use std::io::{BufRead, BufReader};
+#[cfg(target_os = "linux")]
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::Sender;
+
+use crate::monitor::MonitorEvent;
+
+// If the wall clock advanced this much more than the monotonic clock (which does not advance
+// while the system is suspended) then assume the system was suspended
+const SUSPEND_THRESHOLD: Duration = Duration::from_secs(10);
+
+/// [ClockCheck] detects that the system was suspended (and then resumed) between its creation
+/// and a later call to `suspended()`, by comparing how much the wall clock and the monotonic
+/// clock advanced. It works on all platforms, but only after the system has resumed.
+pub(crate) struct ClockCheck {
+    wall: SystemTime,
+    monotonic: Instant,
+}
+
+impl ClockCheck {
+    pub fn new() -> Self {
+        ClockCheck {
+            wall: SystemTime::now(),
+            monotonic: Instant::now(),
+        }
+    }
+
+    /// Return how long the system was suspended for, if it was
+    pub fn suspended(&self) -> Option<Duration> {
+        let wall = SystemTime::now().duration_since(self.wall).ok()?;
+        let gap = wall.checked_sub(self.monotonic.elapsed())?;
+        (gap > SUSPEND_THRESHOLD).then_some(gap)
+    }
+}
+
+/// [SleepWatcher] listens to logind's `PrepareForSleep` signal, sending a [MonitorEvent] on the
+/// channel when the system is about to suspend and when it resumes. While the monitor is running
+/// it holds a "delay" inhibitor lock, so that it has a chance to send a Stop report before the
+/// system suspends. The lock must be released (using `release()`) once that is done.
+#[cfg(target_os = "linux")]
+pub(crate) struct SleepWatcher {
+    monitor: Child,
+    inhibitor: Option<Child>,
+}
+
+#[cfg(target_os = "linux")]
+impl SleepWatcher {
+    /// Start watching for suspend and resume. Failure (e.g. `dbus-monitor` not being installed,
+    /// or no logind) is not fatal, as resumes can still be detected using [ClockCheck]
+    pub fn start(sender: Sender<MonitorEvent>) -> Result<Self, io::Error> {
+        let mut monitor = Command::new("dbus-monitor")
+            .arg("--system")
+            .arg(
+                "type='signal',interface='org.freedesktop.login1.Manager',member='PrepareForSleep'",
+            )
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Could not execute 'dbus-monitor': {e}"),
+                )
+            })?;
+
+        let stdout = monitor.stdout.take().ok_or(io::Error::new(
+            io::ErrorKind::BrokenPipe,
+            "Could not read output of 'dbus-monitor'",
+        ))?;
+
+        std::thread::spawn(move || {
+            let mut parser = SignalParser::default();
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if let Some(event) = parser.parse_line(&line) {
+                    if sender.send(event).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        let mut watcher = SleepWatcher {
+            monitor,
+            inhibitor: None,
+        };
+        watcher.acquire();
+        Ok(watcher)
+    }
+
+    /// Take a delay inhibitor lock, if we don't have one already
+    pub fn acquire(&mut self) {
+        if self.inhibitor.is_none() {
+            self.inhibitor = Command::new("systemd-inhibit")
+                .args([
+                    "--what=sleep",
+                    "--mode=delay",
+                    "--who=wimon",
+                    "--why=Send a Stop report before suspending",
+                    "sleep",
+                    "infinity",
+                ])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .map_err(|e| eprintln!("Could not take a sleep inhibitor lock: {e}"))
+                .ok();
+        }
+    }
+
+    /// Release the inhibitor lock, so that the system can suspend
+    pub fn release(&mut self) {
+        if let Some(mut inhibitor) = self.inhibitor.take() {
+            let _ = inhibitor.kill();
+            let _ = inhibitor.wait();
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for SleepWatcher {
+    fn drop(&mut self) {
+        self.release();
+        let _ = self.monitor.kill();
+        let _ = self.monitor.wait();
+    }
+}
+
+// Parse the output of `dbus-monitor` for `PrepareForSleep` signals. The boolean argument of
+// the signal is on the line following the signal's header line.
+#[cfg(target_os = "linux")]
+#[derive(Default)]
+struct SignalParser {
+    in_prepare_for_sleep: bool,
+}
+
+#[cfg(target_os = "linux")]
+impl SignalParser {
+    fn parse_line(&mut self, line: &str) -> Option<MonitorEvent> {
+        let line = line.trim();
+        if line.starts_with("signal ") {
+            self.in_prepare_for_sleep = line.contains("member=PrepareForSleep");
+            return None;
+        }
+
+        if self.in_prepare_for_sleep {
+            self.in_prepare_for_sleep = false;
+            match line {
+                "boolean true" => return Some(MonitorEvent::Suspending),
+                "boolean false" => return Some(MonitorEvent::Resumed),
+                _ => {}
+            }
+        }
+
+        None
+    }
+}
+
+/// On platforms without logind, resumes are only detected using [ClockCheck]
+#[cfg(not(target_os = "linux"))]
+pub(crate) struct SleepWatcher;
+
+#[cfg(not(target_os = "linux"))]
+impl SleepWatcher {
+    pub fn start(_sender: Sender<MonitorEvent>) -> Result<Self, io::Error> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Suspend notifications are not supported on this platform",
+        ))
+    }
+
+    pub fn acquire(&mut self) {}
+
+    pub fn release(&mut self) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::ClockCheck;
+
+    #[test]
+    fn not_suspended() {
+        assert!(ClockCheck::new().suspended().is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parse_prepare_for_sleep() {
+        use crate::monitor::MonitorEvent;
+
+        let mut parser = super::SignalParser::default();
+        let output = "signal time=1705667293.1 sender=:1.3 -> destination=(null destination) \
+        serial=1027 path=/org/freedesktop/login1; interface=org.freedesktop.login1.Manager; \
+        member=PrepareForSleep\n   boolean true\n\
+        signal time=1705667393.2 sender=:1.3 -> destination=(null destination) \
+        serial=1028 path=/org/freedesktop/login1; interface=org.freedesktop.login1.Manager; \
+        member=PrepareForSleep\n   boolean false\n";
+        let events: Vec<MonitorEvent> = output
+            .lines()
+            .filter_map(|line| parser.parse_line(line))
+            .collect();
+        assert!(matches!(
+            events.as_slice(),
+            [MonitorEvent::Suspending, MonitorEvent::Resumed]
+        ));
+    }
+}
@@ -0,0 +1,123 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use config::Config;
+use data_model::{MonitorReport, ProbeReport};
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::monitor::Source;
+
+const DEFAULT_PROBE_TIMEOUT_MS: u64 = 2000;
+
+/// [ProbeSource] probes each of the targets in the config by making a TCP connection to it,
+/// reporting the time taken to connect or None if it failed
+pub struct ProbeSource {
+    targets: Vec<String>,
+    timeout: Duration,
+}
+
+impl ProbeSource {
+    pub fn new(config: &Config) -> Self {
+        let spec = config.probe.as_ref();
+        ProbeSource {
+            targets: spec
+                .and_then(|spec| spec.targets.clone())
+                .unwrap_or_default(),
+            timeout: Duration::from_millis(
+                spec.and_then(|spec| spec.timeout_ms)
+                    .unwrap_or(DEFAULT_PROBE_TIMEOUT_MS),
+            ),
+        }
+    }
+
+    pub(crate) fn run(&self) -> Vec<ProbeReport> {
+        self.run_via(None)
+    }
+
+    /// Run the probes, bound to `interface` (by name or source address) if given
+    pub(crate) fn run_via(&self, interface: Option<&str>) -> Vec<ProbeReport> {
+        self.targets
+            .iter()
+            .map(|target| ProbeReport {
+                target: target.clone(),
+                latency_ms: match probe(target, self.timeout, interface) {
+                    Ok(latency) => Some(latency.as_millis() as u32),
+                    Err(e) => {
+                        match interface {
+                            Some(interface) => {
+                                eprintln!("Probe of '{target}' via '{interface}' failed: {e}")
+                            }
+                            None => eprintln!("Probe of '{target}' failed: {e}"),
+                        }
+                        None
+                    }
+                },
+                samples: None,
+            })
+            .collect()
+    }
+}
+
+impl Source for ProbeSource {
+    fn measure(&mut self, report: &mut MonitorReport) -> Result<(), io::Error> {
+        report.probes.extend(self.run());
+        Ok(())
+    }
+}
+
+// Make a TCP connection to `target`, through `interface` if given, returning how long it took
+fn probe(target: &str, timeout: Duration, interface: Option<&str>) -> Result<Duration, io::Error> {
+    let address = target.to_socket_addrs()?.next().ok_or(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("Could not resolve '{target}'"),
+    ))?;
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    if let Some(interface) = interface {
+        bind(&socket, interface)?;
+    }
+    let start = Instant::now();
+    socket.connect_timeout(&address.into(), timeout)?;
+    Ok(start.elapsed())
+}
+
+// Bind a socket to an interface, by its address, or by its name using SO_BINDTODEVICE on Linux
+fn bind(socket: &Socket, interface: &str) -> Result<(), io::Error> {
+    if let Ok(ip) = interface.parse::<IpAddr>() {
+        return socket.bind(&SocketAddr::new(ip, 0).into());
+    }
+
+    #[cfg(target_os = "linux")]
+    return socket.bind_device(Some(interface.as_bytes()));
+
+    #[cfg(not(target_os = "linux"))]
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("Can only bind to interface '{interface}' by its address on this platform"),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    #[test]
+    fn probe_local_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        assert!(super::probe(&target, Duration::from_secs(1), None).is_ok());
+    }
+
+    #[test]
+    fn probe_via_source_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        assert!(super::probe(&target, Duration::from_secs(1), Some("127.0.0.1")).is_ok());
+        assert!(super::probe(&target, Duration::from_secs(1), Some("::1")).is_err());
+    }
+}
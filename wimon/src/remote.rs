@@ -0,0 +1,168 @@
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use config::{Config, ConfigError, RemoteSettings};
+use curl::easy::{Easy, List};
+use data_model::DeviceId;
+
+use crate::monitor::Reconfigurer;
+use crate::transport;
+
+const DEFAULT_POLL_SECONDS: u64 = 300;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The response to a request for the remote config
+enum Response {
+    /// The config has not changed since the one with the ETag sent
+    NotModified,
+    /// There is no config for this device
+    NotFound,
+    Config {
+        etag: Option<String>,
+        text: String,
+    },
+}
+
+/// [RemoteConfig] fetches the settings for this device from the collector's config endpoint,
+/// using the ETag of the last ones fetched so that they are only sent again when changed
+pub struct RemoteConfig {
+    url: String,
+    /// The config used for the proxy and TLS options of the requests
+    config: Config,
+    poll_period: Duration,
+    etag: Option<String>,
+    /// The text of the settings last fetched
+    fetched: Option<String>,
+}
+
+impl RemoteConfig {
+    /// Create a [RemoteConfig] if the remote config is enabled
+    pub fn new(config: &Config, device_id: &DeviceId) -> Option<Self> {
+        let mut url = config.remote_url.clone()?;
+        url.query_pairs_mut().append_pair("device_id", device_id);
+        let poll_seconds = config
+            .remote
+            .as_ref()
+            .and_then(|spec| spec.poll_seconds)
+            .unwrap_or(DEFAULT_POLL_SECONDS);
+        Some(RemoteConfig {
+            url: url.to_string(),
+            config: config.clone(),
+            poll_period: Duration::from_secs(poll_seconds),
+            etag: None,
+            fetched: None,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn poll_period(&self) -> Duration {
+        self.poll_period
+    }
+
+    /// Fetch the settings, returning them if they have changed since they were last fetched.
+    /// If the collector has no config for this device the settings are empty, so that only the
+    /// local config is used.
+    pub fn fetch(&mut self) -> Result<Option<RemoteSettings>, io::Error> {
+        let (etag, text) = match self.request()? {
+            Response::NotModified => return Ok(None),
+            Response::NotFound => (None, String::new()),
+            Response::Config { etag, text } => (etag, text),
+        };
+        self.etag = etag;
+        if self.fetched.as_ref() == Some(&text) {
+            return Ok(None);
+        }
+        // Settings that are not valid are only reported once, not every time they are fetched
+        let settings = RemoteSettings::parse(&self.url, &text);
+        self.fetched = Some(text);
+        Ok(Some(settings?))
+    }
+
+    /// Check for changes to the settings every poll period in the background, until the monitor
+    /// stops. When they change `load` makes the config with them, and if it is valid the
+    /// monitor uses it. If the settings can't be fetched the monitor keeps its current config.
+    pub fn watch(
+        mut self,
+        monitor: Reconfigurer,
+        load: impl Fn(Option<&RemoteSettings>) -> Result<Config, ConfigError> + Send + 'static,
+    ) -> Result<(), io::Error> {
+        thread::Builder::new()
+            .name("remote-config".into())
+            .spawn(move || loop {
+                thread::sleep(self.poll_period);
+                match self.fetch().and_then(|settings| {
+                    settings
+                        .map(|settings| load(Some(&settings)).map_err(io::Error::from))
+                        .transpose()
+                }) {
+                    Ok(None) => {}
+                    Ok(Some(config)) => {
+                        println!("Remote config changed");
+                        if !monitor.reconfigure(config) {
+                            break;
+                        }
+                    }
+                    Err(e) => eprintln!("Keeping the current config: {e}"),
+                }
+            })?;
+        Ok(())
+    }
+
+    fn request(&self) -> Result<Response, io::Error> {
+        let mut easy = Easy::new();
+        easy.url(&self.url).map_err(|_| {
+            io::Error::new(io::ErrorKind::NotFound, "Could not set url on curl request")
+        })?;
+        easy.timeout(FETCH_TIMEOUT)?;
+        transport::configure(&mut easy, &self.config)?;
+        if let Some(etag) = &self.etag {
+            let mut headers = List::new();
+            headers.append(&format!("If-None-Match: {etag}"))?;
+            easy.http_headers(headers)?;
+        }
+
+        let mut data = Vec::new();
+        let mut etag = None;
+        {
+            let mut transfer = easy.transfer();
+            transfer.header_function(|header| {
+                if let Some((name, value)) = String::from_utf8_lossy(header).split_once(':') {
+                    if name.trim().eq_ignore_ascii_case("etag") {
+                        etag = Some(value.trim().to_string());
+                    }
+                }
+                true
+            })?;
+            transfer.write_function(|new_data| {
+                data.extend_from_slice(new_data);
+                Ok(new_data.len())
+            })?;
+            transfer
+                .perform()
+                .map_err(|e| self.error(transport::request_error(&e)))?;
+        }
+
+        let text = String::from_utf8_lossy(&data).into_owned();
+        match easy.response_code()? {
+            304 => Ok(Response::NotModified),
+            404 => Ok(Response::NotFound),
+            200 => Ok(Response::Config { etag, text }),
+            code => Err(self.error(io::Error::other(format!(
+                "Collector responded with HTTP status {code}: {}",
+                text.trim()
+            )))),
+        }
+    }
+
+    // An error fetching the settings, saying where from
+    fn error(&self, e: io::Error) -> io::Error {
+        io::Error::new(
+            e.kind(),
+            format!("Could not fetch remote config from '{}': {e}", self.url),
+        )
+    }
+}
@@ -0,0 +1,203 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use config::Config;
+use data_model::{ConnectionReport, MonitorReport, ProbeReport, SampleStats, Stats};
+
+use crate::probe::ProbeSource;
+use crate::wifi::get_signal;
+
+/// [Sampler] takes samples of the signal level and probe latencies at a fixed interval during
+/// each report period, and adds statistics of them to the report at the end of the period
+pub(crate) struct Sampler {
+    interval: Duration,
+    next: Instant,
+    probes: ProbeSource,
+    // How the signal level is read, which the tests replace
+    get_signal: fn() -> Result<i16, io::Error>,
+    signal: Vec<Option<f32>>,
+    latencies: Vec<(String, Vec<Option<f32>>)>,
+}
+
+impl Sampler {
+    /// Create a [Sampler] if sampling is configured
+    pub fn new(config: &Config) -> Option<Self> {
+        let interval = config
+            .sampling
+            .as_ref()
+            .and_then(|spec| spec.interval_seconds)
+            .filter(|seconds| *seconds > 0)
+            .map(Duration::from_secs)?;
+
+        Some(Sampler {
+            interval,
+            next: Instant::now() + interval,
+            probes: ProbeSource::new(config),
+            get_signal,
+            signal: vec![],
+            latencies: vec![],
+        })
+    }
+
+    /// How long until the next sample is due
+    pub fn wait(&self) -> Duration {
+        self.next.saturating_duration_since(Instant::now())
+    }
+
+    /// Take a sample of the signal level and of each probe's latency
+    pub fn sample(&mut self) {
+        self.add_signal_sample();
+        let probes = self.probes.run();
+        self.add_probe_samples(&probes);
+        self.next = Instant::now() + self.interval;
+    }
+
+    fn add_signal_sample(&mut self) {
+        self.signal
+            .push((self.get_signal)().ok().map(|level| level as f32));
+    }
+
+    fn add_probe_samples(&mut self, probes: &[ProbeReport]) {
+        for probe in probes {
+            let latency = probe.latency_ms.map(|latency| latency as f32);
+            match self
+                .latencies
+                .iter_mut()
+                .find(|(target, _)| target == &probe.target)
+            {
+                Some((_, samples)) => samples.push(latency),
+                None => self.latencies.push((probe.target.clone(), vec![latency])),
+            }
+        }
+    }
+
+    /// Add the measurement for the report as the last sample, then add the statistics of the
+    /// samples taken during the period to the report, and start sampling the next period. A
+    /// connection is only added to the report for the signal if there was one to sample (i.e.
+    /// not on Ethernet).
+    pub fn finish(&mut self, report: &mut MonitorReport) {
+        self.add_signal_sample();
+        self.add_probe_samples(&report.probes);
+
+        for probe in report.probes.iter_mut() {
+            if let Some((_, samples)) = self
+                .latencies
+                .iter()
+                .find(|(target, _)| target == &probe.target)
+            {
+                probe.samples = Some(SampleStats::from_samples(samples));
+            }
+        }
+
+        let signal = SampleStats::from_samples(&self.signal);
+        let used = report.connection_used.to_string();
+        match report
+            .connections
+            .iter_mut()
+            .find(|c| c.connection.to_string() == used)
+        {
+            Some(ConnectionReport {
+                stats: Some(stats), ..
+            }) => stats.samples = Some(signal),
+            Some(connection) => connection.stats = signal_stats(signal),
+            None => {
+                if let Some(stats) = signal_stats(signal) {
+                    report.connections.push(ConnectionReport {
+                        connection: report.connection_used.clone(),
+                        stats: Some(stats),
+                        access_points: vec![],
+                        interface: None,
+                        probes: vec![],
+                        link: None,
+                    })
+                }
+            }
+        }
+
+        self.signal.clear();
+        self.latencies.clear();
+        self.next = Instant::now() + self.interval;
+    }
+}
+
+// Stats for a connection, using the mean of the samples as the power, if any succeeded
+fn signal_stats(samples: SampleStats) -> Option<Stats> {
+    samples.mean.map(|mean| Stats {
+        power_dbs: mean.round() as i16,
+        samples: Some(samples),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use config::{Config, SamplingSpec};
+    use data_model::{Connection, MonitorReport, ProbeReport};
+
+    use super::Sampler;
+
+    fn sampler(get_signal: fn() -> Result<i16, io::Error>) -> Sampler {
+        let config = Config {
+            sampling: Some(SamplingSpec {
+                interval_seconds: Some(10),
+            }),
+            ..Config::default()
+        };
+        Sampler {
+            get_signal,
+            ..Sampler::new(&config).unwrap()
+        }
+    }
+
+    #[test]
+    fn no_sampler_without_an_interval() {
+        assert!(Sampler::new(&Config::default()).is_none());
+    }
+
+    #[test]
+    fn stats_of_the_samples_are_added_to_the_report() {
+        let mut sampler = sampler(|| Ok(-60));
+        sampler.add_signal_sample();
+        sampler.signal.push(None);
+        sampler.add_probe_samples(&[ProbeReport {
+            target: "1.1.1.1:53".into(),
+            latency_ms: Some(20),
+            samples: None,
+        }]);
+
+        let mut report = MonitorReport {
+            connection_used: Connection::SSID("MyWifi".into()),
+            probes: vec![ProbeReport {
+                target: "1.1.1.1:53".into(),
+                latency_ms: Some(10),
+                samples: None,
+            }],
+            ..MonitorReport::default()
+        };
+        sampler.finish(&mut report);
+
+        let stats = report.connections[0].stats.as_ref().unwrap();
+        assert_eq!(stats.power_dbs, -60);
+        let signal = stats.samples.as_ref().unwrap();
+        assert_eq!((signal.count, signal.failed), (3, 1));
+        let latency = report.probes[0].samples.as_ref().unwrap();
+        assert_eq!(
+            (latency.count, latency.min, latency.max),
+            (2, Some(10.0), Some(20.0))
+        );
+
+        // Sampling starts again for the next period
+        assert!(sampler.signal.is_empty() && sampler.latencies.is_empty());
+    }
+
+    #[test]
+    fn no_connection_is_added_without_a_signal() {
+        let mut sampler = sampler(|| Err(io::Error::new(io::ErrorKind::NotFound, "Ethernet")));
+        sampler.add_signal_sample();
+
+        let mut report = MonitorReport::default();
+        sampler.finish(&mut report);
+        assert!(report.connections.is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant, SystemTime};
+
+use config::{phase_offset, Config};
+use data_model::DeviceId;
+
+/// [Schedule] keeps the times that reports are due as deadlines on the monotonic clock, each one
+/// period after the previous one, so that the time taken to measure and send a report does not
+/// make the schedule drift. If a deadline is missed completely (e.g. sending took longer than a
+/// period) it is skipped and counted, not shifted.
+pub(crate) struct Schedule {
+    next: Instant,
+    device_id: DeviceId,
+}
+
+impl Schedule {
+    /// Start the schedule. The first report is due one period from now, plus the device's phase
+    /// offset and a random startup delay, if they are configured
+    pub fn start(config: &Config, device_id: &DeviceId, period: Duration) -> Self {
+        let jitter = config
+            .report
+            .as_ref()
+            .and_then(|spec| spec.startup_jitter_seconds)
+            .map(|seconds| random_below(Duration::from_secs(seconds)))
+            .unwrap_or_default();
+        let offset = if has_phase_offset(config) {
+            phase_offset(device_id.as_bytes(), period)
+        } else {
+            Duration::ZERO
+        };
+
+        if !(jitter + offset).is_zero() {
+            println!(
+                "First report delayed by {}s (phase offset {}s)",
+                (jitter + offset).as_secs(),
+                offset.as_secs()
+            );
+        }
+
+        Schedule {
+            next: Instant::now() + period + offset + jitter,
+            device_id: device_id.clone(),
+        }
+    }
+
+    /// How long until the next report is due
+    pub fn wait(&self) -> Duration {
+        self.next.saturating_duration_since(Instant::now())
+    }
+
+    /// The report that was due has been sent, so move on to the next deadline one `period`
+    /// later, skipping any that have already passed. Returns how many were skipped.
+    pub fn advance(&mut self, period: Duration) -> u64 {
+        let now = Instant::now();
+        self.next += period;
+        let mut missed = 0;
+        while self.next <= now {
+            self.next += period;
+            missed += 1;
+        }
+        missed
+    }
+
+    /// Restart the schedule after a report sent out of schedule, e.g. after resuming or with a
+    /// new config, so the next one is due within `period` as the server expects. With a phase
+    /// offset, that is the next time the offset for `period` is reached on the system clock,
+    /// otherwise a full period later.
+    pub fn restart(&mut self, config: &Config, period: Duration) {
+        let wait = if has_phase_offset(config) {
+            let offset = phase_offset(self.device_id.as_bytes(), period);
+            until_phase(offset, period, SystemTime::now())
+        } else {
+            period
+        };
+        self.next = Instant::now() + wait;
+    }
+}
+
+fn has_phase_offset(config: &Config) -> bool {
+    config
+        .report
+        .as_ref()
+        .and_then(|spec| spec.phase_offset)
+        .unwrap_or(false)
+}
+
+// The time after `now`, up to one period, until the system clock is next `offset` into a period
+fn until_phase(offset: Duration, period: Duration, now: SystemTime) -> Duration {
+    let period_millis = period.as_millis();
+    if period_millis == 0 {
+        return Duration::ZERO;
+    }
+    let since_epoch = now
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let offset_millis = offset.as_millis() % period_millis;
+    let into_period = (since_epoch.as_millis() + period_millis - offset_millis) % period_millis;
+    Duration::from_millis((period_millis - into_period) as u64)
+}
+
+// A random duration in [0, max), using the randomly seeded hasher from std
+fn random_below(max: Duration) -> Duration {
+    match max.as_millis() as u64 {
+        0 => Duration::ZERO,
+        millis => Duration::from_millis(RandomState::new().build_hasher().finish() % millis),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread::sleep;
+    use std::time::{Duration, Instant, SystemTime};
+
+    use super::{until_phase, Schedule};
+
+    #[test]
+    fn missed_deadlines_are_skipped() {
+        let period = Duration::from_millis(10);
+        let mut schedule = Schedule {
+            next: Instant::now(),
+            device_id: "device".to_string(),
+        };
+        sleep(Duration::from_millis(35));
+        assert!(schedule.advance(period) >= 3);
+        assert!(schedule.wait() <= period);
+    }
+
+    #[test]
+    fn restart_is_phase_aligned() {
+        let period = Duration::from_secs(60);
+        let at = |seconds| SystemTime::UNIX_EPOCH + Duration::from_secs(seconds);
+        let offset = Duration::from_secs(15);
+        assert_eq!(
+            until_phase(offset, period, at(6000)),
+            Duration::from_secs(15)
+        );
+        assert_eq!(
+            until_phase(offset, period, at(6020)),
+            Duration::from_secs(55)
+        );
+        // Never more than one period away, so the server isn't kept waiting
+        assert_eq!(until_phase(offset, period, at(6015)), period);
+    }
+}
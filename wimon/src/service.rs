@@ -0,0 +1,319 @@
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use service_manager::{
+    ServiceInstallCtx, ServiceLabel, ServiceLevel, ServiceManager, ServiceStartCtx, ServiceStatus,
+    ServiceStatusCtx, ServiceStopCtx, ServiceUninstallCtx,
+};
+
+use config::{SandboxSpec, ServiceSpec};
+
+// How long to wait for the service to stop before giving up on uninstalling it
+const STOP_TIMEOUT: Duration = Duration::from_secs(30);
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub(crate) fn get_service_manager(
+    spec: &ServiceSpec,
+) -> Result<Box<dyn ServiceManager>, io::Error> {
+    // Get generic service by detecting what is available on the platform
+    let mut manager = <dyn ServiceManager>::native()
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Could not create ServiceManager"))?;
+
+    // If no level was requested, leave it at the default level for the platform
+    if let Some(level) = &spec.level {
+        manager.set_level(match level {
+            config::ServiceLevel::System => ServiceLevel::System,
+            config::ServiceLevel::User => ServiceLevel::User,
+        })?;
+    }
+
+    Ok(manager)
+}
+
+// Read the service options from the config file, if one exists
+pub(crate) fn read_service_spec(
+    config_file_path: Option<&PathBuf>,
+) -> Result<ServiceSpec, io::Error> {
+    match config_file_path {
+        Some(path) => Ok(config::read_config(path)?.service.unwrap_or_default()),
+        None => Ok(ServiceSpec::default()),
+    }
+}
+
+// This will install the binary as a service, using the options in the `[service]` section of the
+// config file, and then start it. The service is told explicitly which config file to use.
+pub(crate) fn install_service(
+    service_name: &ServiceLabel,
+    path_to_exec: &str,
+    config_file_path: &Path,
+) -> Result<(), io::Error> {
+    let config = config::read_config(&config_file_path.to_path_buf())?;
+    let spec = config.service.unwrap_or_default();
+    let manager = get_service_manager(&spec)?;
+    let exec_path = PathBuf::from(path_to_exec).canonicalize()?;
+    let working_directory = match &spec.working_directory {
+        Some(dir) => PathBuf::from(dir),
+        None => exec_path
+            .parent()
+            .ok_or(io::Error::new(
+                io::ErrorKind::NotFound,
+                "Could not get exec dir",
+            ))?
+            .to_path_buf(),
+    };
+    let args = vec![
+        OsString::from("--config"),
+        config_file_path.as_os_str().to_owned(),
+    ];
+    let environment = parse_environment(&spec)?;
+
+    let mut ctx = ServiceInstallCtx {
+        label: service_name.clone(),
+        program: exec_path,
+        args,
+        contents: None, // Use the default service definition generated for the platform
+        username: spec.username.clone(),
+        working_directory: Some(working_directory),
+        environment: if environment.is_empty() {
+            None
+        } else {
+            Some(environment)
+        },
+        autostart: true, // autostart on reboot
+        disable_restart_on_failure: false, // restart the monitor if it fails
+    };
+    ctx.contents = service_contents(&ctx, &spec, &config.data_path)?;
+
+    // Install our service using the underlying service management platform
+    manager.install(ctx)?;
+
+    // Start our service using the underlying service management platform
+    manager.start(ServiceStartCtx {
+        label: service_name.clone(),
+    })?;
+
+    println!(
+        "'service '{}' ('{}') installed and started, using config file '{}'",
+        service_name,
+        path_to_exec,
+        config_file_path.display()
+    );
+
+    Ok(())
+}
+
+// this will stop any running instance of the service, wait for it to stop, then uninstall it
+pub(crate) fn uninstall_service(
+    service_name: &ServiceLabel,
+    config_file_path: Option<&PathBuf>,
+) -> Result<(), io::Error> {
+    let spec = read_service_spec(config_file_path)?;
+    let manager = get_service_manager(&spec)?;
+
+    match manager.status(ServiceStatusCtx {
+        label: service_name.clone(),
+    })? {
+        ServiceStatus::NotInstalled => {
+            println!("service '{}' is not installed", service_name);
+            return Ok(());
+        }
+        ServiceStatus::Running => {
+            // Stop our service using the underlying service management platform
+            manager.stop(ServiceStopCtx {
+                label: service_name.clone(),
+            })?;
+            println!("service '{}' stopping", service_name);
+            wait_for_stop(manager.as_ref(), service_name)?;
+            println!("service '{}' stopped", service_name);
+        }
+        ServiceStatus::Stopped(_) => {}
+    }
+
+    // Uninstall our service using the underlying service management platform
+    manager.uninstall(ServiceUninstallCtx {
+        label: service_name.clone(),
+    })?;
+
+    println!("service '{}' uninstalled", service_name);
+
+    Ok(())
+}
+
+// Poll the status of the service until it is no longer running, or timeout
+fn wait_for_stop(
+    manager: &dyn ServiceManager,
+    service_name: &ServiceLabel,
+) -> Result<(), io::Error> {
+    let start = Instant::now();
+    while let ServiceStatus::Running = manager.status(ServiceStatusCtx {
+        label: service_name.clone(),
+    })? {
+        if start.elapsed() > STOP_TIMEOUT {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "service '{}' did not stop within {}s",
+                    service_name,
+                    STOP_TIMEOUT.as_secs()
+                ),
+            ));
+        }
+        std::thread::sleep(STOP_POLL_INTERVAL);
+    }
+
+    Ok(())
+}
+
+// Convert the "NAME=value" environment entries in the spec into (name, value) pairs
+fn parse_environment(spec: &ServiceSpec) -> Result<Vec<(String, String)>, io::Error> {
+    let mut environment = vec![];
+    for entry in spec.environment.as_deref().unwrap_or_default() {
+        let (name, value) = entry.split_once('=').ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Service environment entry '{entry}' is not of the form NAME=value"),
+        ))?;
+        environment.push((name.trim().to_string(), value.to_string()));
+    }
+    Ok(environment)
+}
+
+// Determine the service definition contents to install, if not the default one generated by
+// the service manager for the platform
+fn service_contents(
+    ctx: &ServiceInstallCtx,
+    spec: &ServiceSpec,
+    data_path: &Path,
+) -> Result<Option<String>, io::Error> {
+    if let Some(contents_file) = &spec.contents_file {
+        return std::fs::read_to_string(contents_file)
+            .map(Some)
+            .map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("Could not read service contents file '{contents_file}': {e}"),
+                )
+            });
+    }
+
+    match &spec.sandbox {
+        Some(sandbox) if cfg!(target_os = "linux") => {
+            Ok(Some(systemd_unit(ctx, spec.level, sandbox, data_path)))
+        }
+        Some(_) => {
+            eprintln!("Sandboxing options are only supported for systemd, ignoring them");
+            Ok(None)
+        }
+        None => Ok(None),
+    }
+}
+
+// Generate a systemd unit for the service described by `ctx`, with the sandboxing options requested
+fn systemd_unit(
+    ctx: &ServiceInstallCtx,
+    level: Option<config::ServiceLevel>,
+    sandbox: &SandboxSpec,
+    data_path: &Path,
+) -> String {
+    let working_directory = ctx.working_directory.clone().unwrap_or_default();
+    let mut exec_start = ctx.program.display().to_string();
+    for arg in &ctx.args {
+        exec_start.push_str(&format!(" \"{}\"", arg.to_string_lossy()));
+    }
+
+    let mut unit = format!(
+        "[Unit]\n\
+        Description={}\n\
+        Wants=network-online.target\n\
+        After=network-online.target\n\
+        \n\
+        [Service]\n\
+        ExecStart={exec_start}\n\
+        WorkingDirectory={}\n\
+        Restart=on-failure\n\
+        RestartSec=10\n",
+        ctx.label.to_script_name(),
+        working_directory.display()
+    );
+
+    for (name, value) in ctx.environment.as_deref().unwrap_or_default() {
+        unit.push_str(&format!("Environment=\"{name}={value}\"\n"));
+    }
+
+    if let Some(username) = &ctx.username {
+        unit.push_str(&format!("User={username}\n"));
+    }
+
+    unit.push_str(&format!(
+        "NoNewPrivileges={}\n",
+        sandbox.no_new_privileges.unwrap_or(true)
+    ));
+    unit.push_str(&format!(
+        "ProtectSystem={}\n",
+        sandbox.protect_system.as_deref().unwrap_or("strict")
+    ));
+    unit.push_str(&format!(
+        "ProtectHome={}\n",
+        sandbox.protect_home.as_deref().unwrap_or("read-only")
+    ));
+    unit.push_str(&format!(
+        "PrivateTmp={}\n",
+        sandbox.private_tmp.unwrap_or(true)
+    ));
+
+    // The service must be able to write its state into the data directory
+    let mut read_write_paths = vec![working_directory.display().to_string()];
+    if data_path != working_directory {
+        read_write_paths.push(data_path.display().to_string());
+    }
+    read_write_paths.extend(sandbox.read_write_paths.clone().unwrap_or_default());
+    unit.push_str(&format!("ReadWritePaths={}\n", read_write_paths.join(" ")));
+
+    let wanted_by = match level {
+        Some(config::ServiceLevel::User) => "default.target",
+        _ => "multi-user.target",
+    };
+    unit.push_str(&format!("\n[Install]\nWantedBy={wanted_by}\n"));
+
+    unit
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    use config::SandboxSpec;
+    use service_manager::ServiceInstallCtx;
+
+    #[test]
+    fn sandboxed_unit() {
+        let ctx = ServiceInstallCtx {
+            label: crate::SERVICE_NAME.parse().unwrap(),
+            program: PathBuf::from("/usr/local/bin/wimon"),
+            args: vec![
+                OsString::from("--config"),
+                OsString::from("/etc/wimon/monitor.toml"),
+            ],
+            contents: None,
+            username: Some("wimon".into()),
+            working_directory: Some(PathBuf::from("/opt/wimon")),
+            environment: Some(vec![("RUST_BACKTRACE".into(), "1".into())]),
+            autostart: true,
+            disable_restart_on_failure: false,
+        };
+        let sandbox = SandboxSpec {
+            read_write_paths: Some(vec!["/var/log/wimon".into()]),
+            ..Default::default()
+        };
+        let unit = super::systemd_unit(&ctx, None, &sandbox, &PathBuf::from("/var/lib/wimon"));
+        assert!(unit
+            .contains("ExecStart=/usr/local/bin/wimon \"--config\" \"/etc/wimon/monitor.toml\"\n"));
+        assert!(unit.contains("User=wimon\n"));
+        assert!(unit.contains("Environment=\"RUST_BACKTRACE=1\"\n"));
+        assert!(unit.contains("ProtectSystem=strict\n"));
+        assert!(unit.contains("ReadWritePaths=/opt/wimon /var/lib/wimon /var/log/wimon\n"));
+        assert!(unit.contains("WantedBy=multi-user.target\n"));
+    }
+}
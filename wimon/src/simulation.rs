@@ -0,0 +1,193 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use data_model::{Connection, ConnectionReport, MonitorReport, ProbeReport, Stats};
+use serde_derive::Deserialize;
+
+use crate::monitor::{Report, Sink, Source};
+
+/// A scripted timeline of measurements, read from a toml file with a `[[steps]]` table per step
+#[derive(Deserialize, Debug)]
+struct Timeline {
+    steps: Vec<Step>,
+}
+
+/// One step of a [Timeline], used for `periods` measurements
+#[derive(Deserialize, Debug)]
+struct Step {
+    /// Number of measurements this step is used for. Default: 1
+    periods: Option<u32>,
+    /// The SSID connected to, or an Ethernet connection if not set
+    ssid: Option<String>,
+    signal_dbm: Option<i16>,
+    /// Probe results. A probe without a `latency_ms` failed
+    #[serde(default)]
+    probes: Vec<ProbeReport>,
+    /// The network is down, so reports can't be sent. Default: false
+    #[serde(default)]
+    outage: bool,
+}
+
+/// The position in the timeline, shared by the simulated source and sink
+#[derive(Debug)]
+struct Player {
+    steps: Vec<Step>,
+    index: usize,
+    /// Measurements made in the current step
+    count: u32,
+}
+
+impl Player {
+    // Move to the step to use for the next measurement, repeating the timeline after the last one
+    fn next(&mut self) -> &Step {
+        if self.count >= self.steps[self.index].periods.unwrap_or(1).max(1) {
+            self.index = (self.index + 1) % self.steps.len();
+            self.count = 0;
+        }
+        self.count += 1;
+        &self.steps[self.index]
+    }
+
+    fn current(&self) -> &Step {
+        &self.steps[self.index]
+    }
+}
+
+/// A [Simulation] replays a scripted timeline of SSIDs, signal levels, probe results and outages,
+/// using a [SimulatedSource] in place of the real measurements and a [SimulatedSink] that fails
+/// to send reports during outages
+#[derive(Clone)]
+pub(crate) struct Simulation {
+    player: Arc<Mutex<Player>>,
+}
+
+impl Simulation {
+    pub fn load(timeline_file: &Path) -> Result<Self, io::Error> {
+        let timeline: Timeline =
+            toml::from_str(&fs::read_to_string(timeline_file)?).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Could not parse simulation timeline '{}': {e}",
+                        timeline_file.display()
+                    ),
+                )
+            })?;
+        if timeline.steps.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Simulation timeline '{}' has no steps",
+                    timeline_file.display()
+                ),
+            ));
+        }
+
+        Ok(Simulation {
+            player: Arc::new(Mutex::new(Player {
+                steps: timeline.steps,
+                index: 0,
+                count: 0,
+            })),
+        })
+    }
+
+    pub fn source(&self) -> SimulatedSource {
+        SimulatedSource(self.clone())
+    }
+
+    pub fn sink(&self, sink: impl Sink + 'static) -> SimulatedSink {
+        SimulatedSink {
+            simulation: self.clone(),
+            sink: Box::new(sink),
+        }
+    }
+}
+
+/// Adds the measurements of the next step of the timeline to the report
+pub(crate) struct SimulatedSource(Simulation);
+
+impl Source for SimulatedSource {
+    fn measure(&mut self, report: &mut MonitorReport) -> Result<(), io::Error> {
+        let mut player = self.0.player.lock().unwrap();
+        let step = player.next();
+
+        report.connection_used = match &step.ssid {
+            Some(ssid) => Connection::SSID(ssid.clone()),
+            None => Connection::Ethernet("simulated".into()),
+        };
+        report.connections.push(ConnectionReport {
+            connection: report.connection_used.clone(),
+            stats: step.signal_dbm.map(|power_dbs| Stats {
+                power_dbs,
+                samples: None,
+            }),
+            access_points: vec![],
+            interface: None,
+            probes: vec![],
+            link: None,
+        });
+        report
+            .probes
+            .extend(step.probes.iter().map(|probe| ProbeReport {
+                // Nothing gets through during an outage
+                latency_ms: probe.latency_ms.filter(|_| !step.outage),
+                ..probe.clone()
+            }));
+        Ok(())
+    }
+}
+
+/// Passes reports on to another sink, except during an outage when sending fails
+pub(crate) struct SimulatedSink {
+    simulation: Simulation,
+    sink: Box<dyn Sink>,
+}
+
+impl Sink for SimulatedSink {
+    fn send(&mut self, report: &Report) -> Result<(), io::Error> {
+        if self.simulation.player.lock().unwrap().current().outage {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "Simulated outage: could not send report",
+            ));
+        }
+        self.sink.send(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use data_model::MonitorReport;
+
+    use super::Simulation;
+    use crate::monitor::Source;
+
+    #[test]
+    fn replay_example_timeline() {
+        let timeline = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("simulation.toml");
+        let simulation = Simulation::load(&timeline).unwrap();
+        let mut source = simulation.source();
+        let mut measure = || {
+            let mut report = MonitorReport::default();
+            source.measure(&mut report).unwrap();
+            let outage = simulation.player.lock().unwrap().current().outage;
+            (report, outage)
+        };
+
+        let (report, outage) = measure();
+        assert_eq!(report.connection_used.to_string(), "\tssid=Home");
+        assert_eq!(report.connections[0].stats.as_ref().unwrap().power_dbs, -55);
+        assert_eq!(report.probes[0].latency_ms, Some(12));
+        assert!(!outage);
+
+        // Play through to the outage
+        let (report, outage) = (0..4).map(|_| measure()).last().unwrap();
+        assert!(outage);
+        assert_eq!(report.probes[0].latency_ms, None);
+    }
+}
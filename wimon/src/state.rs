@@ -0,0 +1,141 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use data_model::{DeviceId, MonitorReport, ReportType};
+use serde_derive::{Deserialize, Serialize};
+
+const STATE_FILE_NAME: &str = "wimon.state.json";
+
+/// The most recent measurement taken by the monitor
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Measurement {
+    pub timestamp: u64, // seconds in Unix EPOCH
+    pub report: MonitorReport,
+}
+
+/// The outcome of the most recent attempt to send a report
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SendOutcome {
+    pub timestamp: u64, // seconds in Unix EPOCH
+    pub report_type: String,
+    pub success: bool,
+    pub message: String,
+}
+
+/// [MonitorState] is kept up to date by the running monitor in a small file in the data directory
+/// so that other invocations of wimon (e.g. `wimon status`) can report on what it is doing
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct MonitorState {
+    pub pid: u32,
+    pub device_id: DeviceId,
+    pub started: u64, // seconds in Unix EPOCH
+    pub last_measurement: Option<Measurement>,
+    pub last_send: Option<SendOutcome>,
+    pub consecutive_failures: u32,
+    pub next_report: Option<u64>, // seconds in Unix EPOCH
+    #[serde(default)]
+    pub missed_reports: u64,
+}
+
+impl MonitorState {
+    pub fn new(device_id: &DeviceId) -> Self {
+        MonitorState {
+            pid: std::process::id(),
+            device_id: device_id.clone(),
+            started: now(),
+            ..Default::default()
+        }
+    }
+
+    pub fn measured(&mut self, report: MonitorReport) {
+        self.last_measurement = Some(Measurement {
+            timestamp: now(),
+            report,
+        });
+    }
+
+    pub fn sent(&mut self, report_type: &ReportType, result: &Result<(), io::Error>) {
+        match result {
+            Ok(_) => self.consecutive_failures = 0,
+            Err(_) => self.consecutive_failures += 1,
+        }
+        self.last_send = Some(SendOutcome {
+            timestamp: now(),
+            report_type: report_type.to_string(),
+            success: result.is_ok(),
+            message: match result {
+                Ok(_) => "OK".to_string(),
+                Err(e) => e.to_string(),
+            },
+        });
+    }
+
+    pub fn missed(&mut self, count: u64) {
+        self.missed_reports += count;
+    }
+
+    pub fn scheduled(&mut self, next_report_in: Option<Duration>) {
+        self.next_report = next_report_in.map(|delay| now() + delay.as_secs());
+    }
+
+    /// Write the state to the state file in `data_path`, replacing any previous one
+    pub fn save(&self, data_path: &Path) -> Result<(), io::Error> {
+        fs::create_dir_all(data_path)?;
+        let path = state_file_path(data_path);
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temp_path, &path)
+    }
+
+    /// Load the state saved by a monitor using `data_path`, if there is one
+    pub fn load(data_path: &Path) -> Result<Option<Self>, io::Error> {
+        match fs::read_to_string(state_file_path(data_path)) {
+            Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+pub fn state_file_path(data_path: &Path) -> PathBuf {
+    data_path.join(STATE_FILE_NAME)
+}
+
+/// Seconds since the Unix EPOCH
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod test {
+    use data_model::{MonitorReport, ReportType};
+    use std::io;
+
+    use super::MonitorState;
+    use crate::test_util::test_dir;
+
+    #[test]
+    fn save_and_load() {
+        let dir = test_dir("state");
+
+        let mut state = MonitorState::new(&"device".to_string());
+        state.measured(MonitorReport::default());
+        state.sent(
+            &ReportType::OnGoing,
+            &Err(io::Error::new(io::ErrorKind::NotFound, "failed")),
+        );
+        state.save(&dir).unwrap();
+
+        let loaded = MonitorState::load(&dir).unwrap().unwrap();
+        assert_eq!(loaded.device_id, "device");
+        assert_eq!(loaded.consecutive_failures, 1);
+        assert_eq!(loaded.last_send.unwrap().message, "failed");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
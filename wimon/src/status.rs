@@ -0,0 +1,110 @@
+use std::io;
+use std::path::PathBuf;
+
+use serde_json::json;
+use service_manager::{ServiceLabel, ServiceStatus, ServiceStatusCtx};
+
+use config::ServiceSpec;
+
+use crate::service;
+use wimon::state::{now, state_file_path, MonitorState};
+
+// Get a description of the status of the service from the platform's service manager
+fn service_status(service_name: &ServiceLabel, config: &config::Config) -> String {
+    let default_spec = ServiceSpec::default();
+    let spec = config.service.as_ref().unwrap_or(&default_spec);
+    let status = service::get_service_manager(spec).and_then(|manager| {
+        manager.status(ServiceStatusCtx {
+            label: service_name.clone(),
+        })
+    });
+
+    match status {
+        Ok(ServiceStatus::NotInstalled) => "NotInstalled".to_string(),
+        Ok(ServiceStatus::Running) => "Running".to_string(),
+        Ok(ServiceStatus::Stopped(None)) => "Stopped".to_string(),
+        Ok(ServiceStatus::Stopped(Some(reason))) => format!("Stopped ({reason})"),
+        Err(e) => format!("Unknown ({e})"),
+    }
+}
+
+// Describe a timestamp relative to now, e.g. "42s ago" or "in 18s"
+fn relative(timestamp: u64) -> String {
+    let now = now();
+    if timestamp <= now {
+        format!("{}s ago", now - timestamp)
+    } else {
+        format!("in {}s", timestamp - now)
+    }
+}
+
+/// Print the status of the wimon service and the outcome of its latest report, as a
+/// human-readable summary or as JSON
+pub(crate) fn print_status(
+    service_name: &ServiceLabel,
+    config_file_path: &PathBuf,
+    as_json: bool,
+) -> Result<(), io::Error> {
+    let config = config::read_config(config_file_path)?;
+    let service = service_status(service_name, &config);
+    let state = MonitorState::load(&config.data_path)?;
+
+    if as_json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json!({
+                "service": service,
+                "state_file": state_file_path(&config.data_path),
+                "state": state,
+            }))?
+        );
+        return Ok(());
+    }
+
+    println!("Service '{}': {}", service_name, service);
+    match state {
+        None => println!(
+            "No monitor state found in '{}'",
+            state_file_path(&config.data_path).display()
+        ),
+        Some(state) => {
+            println!("Device ID: {}", state.device_id);
+            println!(
+                "Monitor started: {} (pid {})",
+                relative(state.started),
+                state.pid
+            );
+            match &state.last_measurement {
+                Some(measurement) => println!(
+                    "Last measurement: {}\n{}",
+                    relative(measurement.timestamp),
+                    measurement.report
+                ),
+                None => println!("Last measurement: none"),
+            }
+            match &state.last_send {
+                Some(send) => println!(
+                    "Last {} report: {} - {}",
+                    send.report_type,
+                    relative(send.timestamp),
+                    if send.success {
+                        send.message.clone()
+                    } else {
+                        format!("FAILED: {}", send.message)
+                    }
+                ),
+                None => println!("Last report: none"),
+            }
+            println!("Consecutive failures: {}", state.consecutive_failures);
+            if state.missed_reports > 0 {
+                println!("Missed scheduled reports: {}", state.missed_reports);
+            }
+            match state.next_report {
+                Some(next) => println!("Next report: {}", relative(next)),
+                None => println!("Next report: none scheduled"),
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use data_model::{AccessPoint, Band, Connection, ConnectionReport, MonitorReport, Stats};
+use wifiscanner::Wifi;
+
+/// Add a Wi-Fi network found by a scan to the report. Access points (BSSIDs) with the same SSID
+/// (e.g. the nodes of a mesh) are grouped under the one connection, whose stats are those of
+/// the strongest access point heard.
+pub(crate) fn add_wifi(report: &mut MonitorReport, wifi: &Wifi) {
+    let access_point = access_point(wifi);
+
+    let position = report
+        .connections
+        .iter()
+        .position(|c| matches!(&c.connection, Connection::SSID(ssid) if ssid == &wifi.ssid));
+    let connection = match position {
+        Some(position) => &mut report.connections[position],
+        None => {
+            report.connections.push(ConnectionReport {
+                connection: Connection::SSID(wifi.ssid.clone()),
+                stats: None,
+                access_points: vec![],
+                interface: None,
+                probes: vec![],
+                link: None,
+            });
+            report.connections.last_mut().unwrap()
+        }
+    };
+
+    let strongest = connection
+        .stats
+        .as_ref()
+        .is_none_or(|stats| access_point.power_dbs > stats.power_dbs);
+    if strongest {
+        connection.stats = Some(Stats {
+            power_dbs: access_point.power_dbs,
+            samples: None,
+        });
+    }
+    connection.access_points.push(access_point);
+}
+
+fn access_point(wifi: &Wifi) -> AccessPoint {
+    let channel = parse_channel(&wifi.channel);
+    AccessPoint {
+        bssid: wifi.mac.to_ascii_lowercase(),
+        channel,
+        band: channel.and_then(band),
+        security: Some(wifi.security.trim().to_string()).filter(|s| !s.is_empty()),
+        // Signal level is an integer on macOS, but can have decimals on linux e.g. "-54.00"
+        power_dbs: wifi
+            .signal_level
+            .trim()
+            .parse::<f32>()
+            .map(|level| level.round() as i16)
+            .unwrap_or(0),
+    }
+}
+
+// The channel can include the width or secondary channel after the number, e.g. "149,+1" or "36,80"
+fn parse_channel(channel: &str) -> Option<u16> {
+    let digits: String = channel
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+// The band told from the channel number. 6GHz channels reuse the numbers of the other bands,
+// so an access point on one can't be told apart, and the scanner doesn't report them
+fn band(channel: u16) -> Option<Band> {
+    match channel {
+        1..=14 => Some(Band::TwoPointFourGHz),
+        32..=177 => Some(Band::FiveGHz),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use data_model::{Band, Connection, MonitorReport};
+    use wifiscanner::Wifi;
+
+    use super::add_wifi;
+
+    fn wifi(mac: &str, ssid: &str, channel: &str, signal_level: &str) -> Wifi {
+        Wifi {
+            mac: mac.into(),
+            ssid: ssid.into(),
+            channel: channel.into(),
+            signal_level: signal_level.into(),
+            security: "WPA2(PSK/AES/AES)".into(),
+        }
+    }
+
+    #[test]
+    fn access_points_grouped_by_ssid() {
+        let mut report = MonitorReport::default();
+        add_wifi(&mut report, &wifi("AA:BB:CC:00:00:01", "Mesh", "6", "-70"));
+        add_wifi(
+            &mut report,
+            &wifi("AA:BB:CC:00:00:02", "Mesh", "149,+1", "-54.00"),
+        );
+        add_wifi(
+            &mut report,
+            &wifi("AA:BB:CC:00:00:03", "Other", "11", "-80"),
+        );
+
+        assert_eq!(report.connections.len(), 2);
+        let mesh = &report.connections[0];
+        assert!(matches!(&mesh.connection, Connection::SSID(ssid) if ssid == "Mesh"));
+        assert_eq!(mesh.stats.as_ref().unwrap().power_dbs, -54);
+        assert_eq!(mesh.access_points.len(), 2);
+        assert_eq!(mesh.access_points[0].band, Some(Band::TwoPointFourGHz));
+        assert_eq!(mesh.access_points[1].bssid, "aa:bb:cc:00:00:02");
+        assert_eq!(mesh.access_points[1].channel, Some(149));
+        assert_eq!(mesh.access_points[1].band, Some(Band::FiveGHz));
+    }
+}
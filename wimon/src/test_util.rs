@@ -0,0 +1,10 @@
+//! Helpers shared by the unit tests of the library and the binary
+
+use std::path::PathBuf;
+
+/// A directory for the test `name`, unique to the test process, created if it doesn't exist
+pub(crate) fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wimon-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,252 @@
+use std::io;
+use std::process::Command;
+
+use config::{fnv1a, Config, TraceProtocol};
+use data_model::{Hop, MonitorReport, PathReport};
+
+use crate::monitor::Source;
+
+const DEFAULT_EVERY_PERIODS: u32 = 10;
+const DEFAULT_MAX_HOPS: u8 = 30;
+const DEFAULT_TIMEOUT_MS: u64 = 1000;
+
+/// [TraceSource] traces the network path to each of the targets in the config every few
+/// periods using `traceroute`. Each report includes the fingerprint of the latest path to each
+/// target, and the full list of hops when the path has changed.
+pub struct TraceSource {
+    targets: Vec<String>,
+    every_periods: u32,
+    max_hops: u8,
+    timeout_ms: u64,
+    protocol: TraceProtocol,
+    /// Number of periods since the paths were last traced
+    periods: u32,
+    /// The latest path traced to each target, with the address each hop last replied from
+    paths: Vec<PathReport>,
+}
+
+impl TraceSource {
+    /// Create a [TraceSource] if any trace targets are configured
+    pub fn new(config: &Config) -> Option<Self> {
+        let spec = config.trace.as_ref()?;
+        let targets = spec.targets.clone().filter(|targets| !targets.is_empty())?;
+
+        Some(TraceSource {
+            targets,
+            every_periods: spec.every_periods.unwrap_or(DEFAULT_EVERY_PERIODS).max(1),
+            max_hops: spec.max_hops.unwrap_or(DEFAULT_MAX_HOPS),
+            timeout_ms: spec.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS),
+            protocol: spec.protocol.unwrap_or_default(),
+            periods: 0,
+            paths: vec![],
+        })
+    }
+
+    // Record the path traced to `target`, returning it with the hops if it has changed. Only
+    // the hops that replied are compared: a hop that didn't reply this time is taken to be
+    // the router that last replied at its TTL.
+    fn traced(&mut self, target: &str, hops: Vec<Hop>) -> PathReport {
+        let previous = self.paths.iter().position(|path| path.target == target);
+        let previous_hops = previous
+            .and_then(|index| self.paths[index].hops.as_deref())
+            .unwrap_or_default();
+        let known: Vec<Hop> = hops
+            .iter()
+            .map(|hop| match &hop.address {
+                Some(_) => hop.clone(),
+                None => previous_hops
+                    .iter()
+                    .find(|previous| previous.ttl == hop.ttl)
+                    .cloned()
+                    .unwrap_or_else(|| hop.clone()),
+            })
+            .collect();
+        let path = PathReport {
+            target: target.to_string(),
+            fingerprint: fingerprint(&known),
+            hops: Some(known),
+        };
+        let changed = match previous {
+            Some(index) => {
+                let changed = self.paths[index].fingerprint != path.fingerprint;
+                self.paths[index] = path.clone();
+                changed
+            }
+            None => {
+                self.paths.push(path.clone());
+                true
+            }
+        };
+
+        PathReport {
+            hops: changed.then_some(hops),
+            ..path
+        }
+    }
+
+    // The latest path traced to `target`, without the hops as it hasn't changed
+    fn latest(&self, target: &str) -> Option<PathReport> {
+        self.paths
+            .iter()
+            .find(|path| path.target == target)
+            .map(|path| PathReport {
+                hops: None,
+                ..path.clone()
+            })
+    }
+}
+
+impl Source for TraceSource {
+    fn measure(&mut self, report: &mut MonitorReport) -> Result<(), io::Error> {
+        for target in self.targets.clone() {
+            if self.periods != 0 {
+                report.paths.extend(self.latest(&target));
+                continue;
+            }
+            match trace(&target, self.max_hops, self.timeout_ms, self.protocol) {
+                Ok(hops) => report.paths.push(self.traced(&target, hops)),
+                Err(e) => {
+                    eprintln!("Trace of '{target}' failed: {e}");
+                    report.paths.extend(self.latest(&target));
+                }
+            }
+        }
+        self.periods = (self.periods + 1) % self.every_periods;
+
+        Ok(())
+    }
+}
+
+/// A fingerprint of a path, from the addresses of its hops, that changes when the path changes.
+/// Hops that didn't reply are left out, as a router that doesn't always reply (e.g. because it
+/// rate limits its replies) is not a change of the path.
+fn fingerprint(hops: &[Hop]) -> String {
+    let addresses: Vec<&str> = hops
+        .iter()
+        .filter_map(|hop| hop.address.as_deref())
+        .collect();
+    format!("{:016x}", fnv1a(addresses.join(",").as_bytes()))
+}
+
+// Trace the path to `target` with `traceroute`, sending one probe per hop
+fn trace(
+    target: &str,
+    max_hops: u8,
+    timeout_ms: u64,
+    protocol: TraceProtocol,
+) -> Result<Vec<Hop>, io::Error> {
+    let mut command = Command::new("traceroute");
+    command.args([
+        "-n",
+        "-q",
+        "1",
+        "-w",
+        &timeout_ms.div_ceil(1000).max(1).to_string(),
+        "-m",
+        &max_hops.to_string(),
+    ]);
+    if protocol == TraceProtocol::Icmp {
+        command.arg("-I");
+    }
+    let output = command
+        .arg(target)
+        .output()
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Could not execute 'traceroute'"))?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    let hops = parse_traceroute(&String::from_utf8_lossy(&output.stdout));
+    if hops.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "Could not parse traceroute output",
+        ));
+    }
+    Ok(hops)
+}
+
+// Parse the output of `traceroute -n -q 1`, with one line per hop e.g. " 2  10.0.0.1  5.678 ms"
+// or " 3  *" if there was no reply
+fn parse_traceroute(data: &str) -> Vec<Hop> {
+    data.lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let ttl = words.next()?.parse().ok()?;
+            let address = words.next().filter(|address| *address != "*");
+            Some(Hop {
+                ttl,
+                address: address.map(|address| address.to_string()),
+                rtt_ms: address.and(words.next()).and_then(|rtt| rtt.parse().ok()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use config::{Config, TraceSpec};
+    use data_model::Hop;
+
+    use super::{parse_traceroute, TraceSource};
+
+    #[test]
+    fn parse_hops() {
+        let output = "traceroute to 1.1.1.1 (1.1.1.1), 30 hops max, 60 byte packets\n\
+         1  192.168.1.1  1.234 ms\n 2  *\n 3  1.1.1.1  12.5 ms !H\n";
+        assert_eq!(
+            parse_traceroute(output),
+            [
+                Hop {
+                    ttl: 1,
+                    address: Some("192.168.1.1".into()),
+                    rtt_ms: Some(1.234)
+                },
+                Hop {
+                    ttl: 2,
+                    address: None,
+                    rtt_ms: None
+                },
+                Hop {
+                    ttl: 3,
+                    address: Some("1.1.1.1".into()),
+                    rtt_ms: Some(12.5)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn hops_reported_when_path_changes() {
+        let config = Config {
+            trace: Some(TraceSpec {
+                targets: Some(vec!["1.1.1.1".into()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut source = TraceSource::new(&config).unwrap();
+        let hops = |address: &str| {
+            parse_traceroute(&format!(" 1  192.168.1.1  1.0 ms\n 2  {address}  5.0 ms\n"))
+        };
+
+        let first = source.traced("1.1.1.1", hops("10.0.0.1"));
+        assert!(first.hops.is_some());
+        let same = source.traced("1.1.1.1", hops("10.0.0.1"));
+        assert_eq!(same.fingerprint, first.fingerprint);
+        assert!(same.hops.is_none());
+        // A hop that doesn't reply is not a change of the path
+        let no_reply = source.traced(
+            "1.1.1.1",
+            parse_traceroute(" 1  192.168.1.1  1.0 ms\n 2  *\n"),
+        );
+        assert_eq!(no_reply.fingerprint, first.fingerprint);
+        assert!(no_reply.hops.is_none());
+        let changed = source.traced("1.1.1.1", hops("10.0.0.2"));
+        assert_ne!(changed.fingerprint, first.fingerprint);
+        assert_eq!(changed.hops.unwrap().len(), 2);
+    }
+}
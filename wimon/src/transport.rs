@@ -0,0 +1,281 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use config::{Config, TlsSpec};
+use curl::easy::Easy;
+
+// Where the system's bundle of trusted CA certificates is commonly found
+const SYSTEM_CA_BUNDLES: [&str; 4] = [
+    "/etc/ssl/certs/ca-certificates.crt", // Debian, Ubuntu, Raspberry Pi OS
+    "/etc/pki/tls/certs/ca-bundle.crt",   // Fedora, RHEL
+    "/etc/ssl/cert.pem",                  // macOS, Alpine
+    "/etc/ssl/ca-bundle.pem",             // openSUSE
+];
+
+/// Set the proxy and TLS options from the `[report]` section of the config on a curl request
+pub(crate) fn configure(easy: &mut Easy, config: &Config) -> Result<(), io::Error> {
+    let spec = match &config.report {
+        Some(spec) => spec,
+        None => return Ok(()),
+    };
+
+    if let Some(proxy) = &spec.proxy {
+        easy.proxy(proxy)
+            .map_err(|e| option_error("proxy", proxy, e))?;
+    }
+    if let Some(no_proxy) = &spec.no_proxy {
+        let hosts = no_proxy.join(",");
+        easy.noproxy(&hosts)
+            .map_err(|e| option_error("no_proxy", &hosts, e))?;
+    }
+    if let Some(tls) = &spec.tls {
+        configure_tls(easy, tls)?;
+    }
+
+    Ok(())
+}
+
+fn configure_tls(easy: &mut Easy, tls: &TlsSpec) -> Result<(), io::Error> {
+    if let Some(ca_files) = &tls.ca_files {
+        let bundle = ca_bundle(ca_files)?;
+        easy.ssl_cainfo_blob(&bundle)
+            .map_err(|e| option_error("tls.ca_files", &ca_files.join(", "), e))?;
+    }
+
+    match (&tls.client_cert, &tls.client_key) {
+        (Some(cert), Some(key)) => {
+            check_readable("tls.client_cert", cert)?;
+            check_readable("tls.client_key", key)?;
+            easy.ssl_cert(cert)
+                .map_err(|e| option_error("tls.client_cert", cert, e))?;
+            easy.ssl_key(key)
+                .map_err(|e| option_error("tls.client_key", key, e))?;
+        }
+        (None, None) => {}
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Both 'client_cert' and 'client_key' must be given in [report.tls] for mutual TLS",
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+// Setting the CA certificates to trust replaces the default ones, so start with the system's
+// bundle (if found) and add the configured ones to it
+fn ca_bundle(ca_files: &[String]) -> Result<Vec<u8>, io::Error> {
+    let mut bundle = SYSTEM_CA_BUNDLES
+        .iter()
+        .find_map(|path| fs::read(path).ok())
+        .unwrap_or_default();
+
+    for ca_file in ca_files {
+        check_readable("tls.ca_files", ca_file)?;
+        let pem = fs::read(ca_file)?;
+        if !String::from_utf8_lossy(&pem).contains("-----BEGIN CERTIFICATE-----") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("CA file '{ca_file}' in [report.tls] 'ca_files' is not a PEM certificate"),
+            ));
+        }
+        bundle.push(b'\n');
+        bundle.extend(pem);
+    }
+
+    Ok(bundle)
+}
+
+fn check_readable(option: &str, path: &str) -> Result<(), io::Error> {
+    fs::metadata(Path::new(path)).map(|_| ()).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Could not read '{path}' given as '{option}' in [report]: {e}"),
+        )
+    })
+}
+
+fn option_error(option: &str, value: &str, e: curl::Error) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Could not use '{value}' for '{option}' in [report]: {e}"),
+    )
+}
+
+/// Describe the error from a curl request, with a hint of what to check in the config if the
+/// problem was with the proxy or TLS
+pub(crate) fn request_error(e: &curl::Error) -> io::Error {
+    let hint = if e.is_couldnt_resolve_proxy() {
+        "could not resolve the proxy, check 'proxy' in [report]"
+    } else if e.is_peer_failed_verification() || e.is_ssl_cacert() || e.is_ssl_issuer_error() {
+        "the server's certificate could not be verified, check 'ca_files' in [report.tls]"
+    } else if e.is_ssl_cacert_badfile() {
+        "could not load the CA certificates, check 'ca_files' in [report.tls]"
+    } else if e.is_ssl_certproblem() {
+        "problem with the client certificate or key, check 'client_cert' and 'client_key' in \
+        [report.tls] (the key must not be encrypted)"
+    } else if e.is_ssl_connect_error() {
+        "TLS handshake failed, the server may require a client certificate (see [report.tls])"
+    } else {
+        ""
+    };
+
+    let message = match e.extra_description() {
+        Some(extra) => format!("{} ({extra})", e.description()),
+        None => e.description().to_string(),
+    };
+
+    if hint.is_empty() {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Could not perform curl request: {message}"),
+        )
+    } else {
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Could not perform curl request: {message}: {hint}"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use config::{Config, ReportSpec, TlsSpec};
+    use curl::easy::Easy;
+
+    use super::{ca_bundle, configure};
+    use crate::test_util::test_dir;
+
+    fn config(report: ReportSpec) -> Config {
+        Config {
+            report: Some(report),
+            ..Config::default()
+        }
+    }
+
+    // Answer `count` requests with an empty response, returning the request line of each
+    fn serve(listener: TcpListener, count: usize) -> thread::JoinHandle<Vec<String>> {
+        thread::spawn(move || {
+            let mut request_lines = vec![];
+            for stream in listener.incoming().take(count) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                request_lines.push(line.trim_end().to_string());
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                    .unwrap();
+            }
+            request_lines
+        })
+    }
+
+    #[test]
+    fn requests_go_through_the_proxy_unless_no_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = serve(listener, 2);
+
+        let proxied = config(ReportSpec {
+            proxy: Some(format!("http://{address}")),
+            ..ReportSpec::default()
+        });
+        let mut easy = Easy::new();
+        configure(&mut easy, &proxied).unwrap();
+        easy.url("http://wimon.invalid/report").unwrap();
+        easy.perform().unwrap();
+
+        let direct = config(ReportSpec {
+            proxy: Some(format!("http://{address}")),
+            no_proxy: Some(vec!["127.0.0.1".to_string()]),
+            ..ReportSpec::default()
+        });
+        let mut easy = Easy::new();
+        configure(&mut easy, &direct).unwrap();
+        easy.url(&format!("http://{address}/report")).unwrap();
+        easy.perform().unwrap();
+
+        assert_eq!(
+            server.join().unwrap(),
+            [
+                "GET http://wimon.invalid/report HTTP/1.1",
+                "GET /report HTTP/1.1"
+            ]
+        );
+    }
+
+    #[test]
+    fn tls_options_are_checked() {
+        let dir = test_dir("tls");
+        let pem = dir.join("client.pem").to_string_lossy().to_string();
+        std::fs::write(
+            &pem,
+            "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+        let tls = |tls: TlsSpec| {
+            configure(
+                &mut Easy::new(),
+                &config(ReportSpec {
+                    tls: Some(tls),
+                    ..ReportSpec::default()
+                }),
+            )
+        };
+
+        let ca_and_client = TlsSpec {
+            ca_files: Some(vec![pem.clone()]),
+            client_cert: Some(pem.clone()),
+            client_key: Some(pem.clone()),
+            ..TlsSpec::default()
+        };
+        assert!(tls(ca_and_client).is_ok());
+
+        let cert_only = TlsSpec {
+            client_cert: Some(pem.clone()),
+            ..TlsSpec::default()
+        };
+        let error = tls(cert_only).unwrap_err();
+        assert!(error.to_string().contains("'client_key'"), "{error}");
+
+        let missing_key = TlsSpec {
+            client_cert: Some(pem),
+            client_key: Some("/no/such/client.key".to_string()),
+            ..TlsSpec::default()
+        };
+        let error = tls(missing_key).unwrap_err();
+        assert!(error.to_string().contains("'tls.client_key'"), "{error}");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ca_file_must_be_pem() {
+        let dir = test_dir("ca");
+        let ca_file = dir.join("ca.pem");
+        let ca_file_string = ca_file.to_string_lossy().to_string();
+
+        std::fs::write(&ca_file, "not a certificate").unwrap();
+        assert!(ca_bundle(std::slice::from_ref(&ca_file_string)).is_err());
+
+        std::fs::write(
+            &ca_file,
+            "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+        let bundle = ca_bundle(&[ca_file_string]).unwrap();
+        assert!(String::from_utf8_lossy(&bundle).ends_with("-----END CERTIFICATE-----\n"));
+
+        assert!(ca_bundle(&["/no/such/ca.pem".to_string()]).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
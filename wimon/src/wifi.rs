@@ -0,0 +1,251 @@
+use std::io;
+use std::process::Command;
+
+use config::Config;
+#[cfg(feature = "ssids")]
+use config::MonitorSpec;
+use data_model::{Connection, MonitorReport};
+
+use crate::monitor::Source;
+#[cfg(feature = "ssids")]
+use crate::survey::add_wifi;
+
+/// [WifiSource] measures the Wi-Fi connection in use and, with the `ssids` feature, the Wi-Fi
+/// networks that are visible (only the one in use unless the config has `monitor = "All"`)
+pub struct WifiSource {
+    #[cfg(feature = "ssids")]
+    monitor: MonitorSpec,
+}
+
+impl WifiSource {
+    #[cfg_attr(not(feature = "ssids"), allow(unused_variables))]
+    pub fn new(config: &Config) -> Self {
+        WifiSource {
+            #[cfg(feature = "ssids")]
+            monitor: config.monitor.unwrap_or(MonitorSpec::Connection),
+        }
+    }
+}
+
+impl Source for WifiSource {
+    fn measure(&mut self, report: &mut MonitorReport) -> Result<(), io::Error> {
+        let ssid = get_ssid().map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Could not get SSID: '{e}'"),
+            )
+        })?;
+        report.connection_used = Connection::SSID(ssid.clone());
+
+        #[cfg(feature = "ssids")]
+        match self.monitor {
+            MonitorSpec::All => {
+                let wifis = wifiscanner::scan().unwrap_or_default();
+                for wifi in wifis {
+                    add_wifi(report, &wifi);
+                }
+            }
+            MonitorSpec::Connection => {
+                let wifis = wifiscanner::scan().unwrap_or_default();
+                for wifi in wifis {
+                    if wifi.ssid == ssid {
+                        add_wifi(report, &wifi);
+                    }
+                }
+            }
+        };
+
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn get_ssid() -> Result<String, io::Error> {
+    get_interface_ssid("en0")
+}
+
+#[cfg(target_os = "macos")]
+fn get_interface_ssid(interface: &str) -> Result<String, io::Error> {
+    let output = Command::new("/usr/sbin/networksetup")
+        .arg("-getairportnetwork")
+        .arg(interface)
+        .output()
+        .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))?;
+
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{}", String::from_utf8_lossy(&output.stderr)),
+        ));
+    }
+
+    let data = String::from_utf8_lossy(&output.stdout);
+
+    parse_ssid(&data)
+}
+
+#[cfg(target_os = "macos")]
+fn parse_ssid(data: &str) -> Result<String, io::Error> {
+    for line in data.lines() {
+        let mut pair = line.trim().split(':');
+        if pair.next().unwrap() == "Current Wi-Fi Network" {
+            return Ok(pair.next().unwrap().trim().to_owned());
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("Could not parse SSID name: '{data}'"),
+    ))
+}
+
+// This will need improving for the case when there are multiple interfaces
+#[cfg(target_os = "linux")]
+fn get_ssid() -> Result<String, io::Error> {
+    let output = Command::new("iw")
+        .arg("dev")
+        .output()
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Could not execute 'iw'"))?;
+    let data = String::from_utf8_lossy(&output.stdout);
+    parse_ssid(&data)
+}
+
+#[cfg(target_os = "linux")]
+fn parse_ssid(data: &str) -> Result<String, io::Error> {
+    for line in data.lines() {
+        let mut pair = line.trim().split(' ');
+        if pair.next().unwrap() == "ssid" {
+            return Ok(pair.next().unwrap().trim().to_owned());
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "Could not parse SSID name",
+    ))
+}
+
+/// Get the signal level (in dBm) of the Wi-Fi connection in use
+#[cfg(target_os = "macos")]
+pub(crate) fn get_signal() -> Result<i16, io::Error> {
+    let output = Command::new(
+        "/System/Library/PrivateFrameworks/Apple80211.framework/Versions/Current/Resources/airport",
+    )
+    .arg("-I")
+    .output()
+    .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))?;
+    let data = String::from_utf8_lossy(&output.stdout);
+    parse_signal(&data, "agrCtlRSSI:")
+}
+
+/// Get the SSID of the Wi-Fi connection on `interface` (the signal level is not available)
+#[cfg(target_os = "macos")]
+pub(crate) fn get_link(interface: &str) -> Result<(String, Option<i16>), io::Error> {
+    Ok((get_interface_ssid(interface)?, None))
+}
+
+/// Get the signal level (in dBm) of the Wi-Fi connection in use
+#[cfg(target_os = "linux")]
+pub(crate) fn get_signal() -> Result<i16, io::Error> {
+    parse_signal(&iw_link(&get_connected_interface()?)?, "signal:")
+}
+
+/// Get the name of the first interface that is connected to Wi-Fi
+#[cfg(target_os = "linux")]
+pub(crate) fn get_connected_interface() -> Result<String, io::Error> {
+    let output = Command::new("iw")
+        .arg("dev")
+        .output()
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Could not execute 'iw'"))?;
+    parse_connected_interface(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Get the SSID and signal level (in dBm) of the Wi-Fi connection on `interface`
+#[cfg(target_os = "linux")]
+pub(crate) fn get_link(interface: &str) -> Result<(String, Option<i16>), io::Error> {
+    let data = iw_link(interface)?;
+    Ok((parse_link_ssid(&data)?, parse_signal(&data, "signal:").ok()))
+}
+
+#[cfg(target_os = "linux")]
+fn iw_link(interface: &str) -> Result<String, io::Error> {
+    let output = Command::new("iw")
+        .args(["dev", interface, "link"])
+        .output()
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Could not execute 'iw'"))?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Parse the SSID from the output of `iw dev <interface> link`, e.g. "SSID: MyWifi"
+#[cfg(target_os = "linux")]
+fn parse_link_ssid(data: &str) -> Result<String, io::Error> {
+    data.lines()
+        .find_map(|line| line.trim().strip_prefix("SSID:"))
+        .map(|ssid| ssid.trim().to_owned())
+        .ok_or(io::Error::new(
+            io::ErrorKind::NotFound,
+            "Interface is not connected to Wi-Fi",
+        ))
+}
+
+// Find the first interface in the output of `iw dev` that is connected to an SSID
+#[cfg(target_os = "linux")]
+fn parse_connected_interface(data: &str) -> Result<String, io::Error> {
+    let mut interface = None;
+    for line in data.lines() {
+        let mut pair = line.trim().split(' ');
+        match pair.next() {
+            Some("Interface") => interface = pair.next().map(|name| name.to_owned()),
+            Some("ssid") if interface.is_some() => return Ok(interface.unwrap_or_default()),
+            _ => {}
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "Could not find an interface connected to Wi-Fi",
+    ))
+}
+
+// Parse the signal level from the line starting with `label` e.g. "signal: -54 dBm"
+fn parse_signal(data: &str, label: &str) -> Result<i16, io::Error> {
+    for line in data.lines() {
+        if let Some(value) = line.trim().strip_prefix(label) {
+            if let Some(Ok(level)) = value.split_whitespace().next().map(|v| v.parse::<i16>()) {
+                return Ok(level);
+            }
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "Could not parse signal level",
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn parse_signal() {
+        let link = "Connected to aa:bb:cc:dd:ee:ff (on wlan0)\n\tSSID: MyWifi\n\tfreq: 5180\n\
+        \tsignal: -54 dBm\n\ttx bitrate: 433.3 MBit/s\n";
+        assert_eq!(super::parse_signal(link, "signal:").unwrap(), -54);
+        assert!(super::parse_signal("Not connected.", "signal:").is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parse_link_ssid() {
+        let link = "Connected to aa:bb:cc:dd:ee:ff (on wlan0)\n\tSSID: My Wifi\n\tfreq: 5180\n";
+        assert_eq!(super::parse_link_ssid(link).unwrap(), "My Wifi");
+        assert!(super::parse_link_ssid("Not connected.").is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parse_connected_interface() {
+        let dev = "phy#1\n\tInterface wlan1\n\t\tifindex 4\n\t\ttype managed\n\
+        phy#0\n\tInterface wlan0\n\t\tifindex 3\n\t\tssid MyWifi\n\t\ttype managed\n";
+        assert_eq!(super::parse_connected_interface(dev).unwrap(), "wlan0");
+    }
+}
@@ -0,0 +1,187 @@
+//! End-to-end tests of the contract between wimon and collectr's `/report/:type` route, running
+//! the monitor (with simulated measurements) against a local mock collector
+
+mod mock_collector;
+
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use data_model::{Connection, MonitorReport, ReportType};
+use wimon::state::{state_file_path, MonitorState};
+use wimon::Monitor;
+
+use mock_collector::{MockCollector, Request};
+
+const DEVICE_ID: &str = "e2e-device";
+// An SSID with characters that must be encoded in the query string and the form
+const SSID: &str = "Café & Bar+1";
+
+const TIMELINE: &str = r#"
+[[steps]]
+ssid = "Café & Bar+1"
+signal_dbm = -55
+probes = [{ target = "1.1.1.1:53", latency_ms = 12 }]
+"#;
+
+// Read a config that simulates the timeline, written to a new directory for the test `name`
+fn config(name: &str, collector: &MockCollector, period: Duration) -> (config::Config, PathBuf) {
+    let path = collector.config_file(name, TIMELINE, "");
+    let mut config = config::read_config(&path).unwrap();
+    config.period_duration = period;
+    (config, path.parent().unwrap().to_path_buf())
+}
+
+fn monitor(config: config::Config) -> Monitor {
+    Monitor::builder(config)
+        .device_id(DEVICE_ID)
+        .build()
+        .unwrap()
+}
+
+// Wait for the collector to receive at least `count` requests
+fn wait_for_requests(collector: &MockCollector, count: usize) -> Vec<Request> {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let requests = collector.requests();
+        if requests.len() >= count || Instant::now() > deadline {
+            return requests;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn ongoing_reports_then_stop() {
+    let collector = MockCollector::start();
+    let (config, dir) = config("ongoing", &collector, Duration::from_millis(100));
+
+    let handle = monitor(config).start().unwrap();
+    wait_for_requests(&collector, 2);
+    handle.stop().unwrap();
+
+    let requests = collector.requests();
+    let (stop, ongoing) = requests.split_last().unwrap();
+    assert!(ongoing.len() >= 2);
+    assert!(ongoing.iter().all(|r| r.path == "/report/ongoing"));
+    assert_eq!(stop.path, "/report/stop");
+    for request in &requests {
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.query("device_id"), Some(DEVICE_ID));
+        assert_eq!(request.query("connection"), Some(&*format!("ssid={SSID}")));
+        assert_eq!(request.query("period"), Some("0"));
+        assert_eq!(request.query("reason"), None);
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn report_is_form_encoded() {
+    let collector = MockCollector::start();
+    let (config, dir) = config("form", &collector, Duration::from_secs(60));
+
+    let mut monitor = monitor(config);
+    let report = monitor.measure();
+    monitor
+        .send(&ReportType::OnGoing, Some("test"), &report)
+        .unwrap();
+
+    let requests = collector.requests();
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    assert_eq!(
+        request.content_type.as_deref(),
+        Some("application/x-www-form-urlencoded")
+    );
+    assert_eq!(request.query("period"), Some("60"));
+    assert_eq!(request.query("reason"), Some("test"));
+    // The '&' in the SSID must not split the form into more fields
+    assert_eq!(request.body.split('&').count(), 1);
+
+    let sent: MonitorReport = serde_json::from_str(&request.form("report").unwrap()).unwrap();
+    assert!(matches!(&sent.connection_used, Connection::SSID(ssid) if ssid == SSID));
+    assert_eq!(sent.probes[0].target, "1.1.1.1:53");
+    assert_eq!(sent.probes[0].latency_ms, Some(12));
+    assert_eq!(
+        sent.connections[0]
+            .stats
+            .as_ref()
+            .map(|stats| stats.power_dbs),
+        Some(-55)
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn failed_report_is_an_error() {
+    let collector = MockCollector::start();
+    let (config, dir) = config("failed", &collector, Duration::from_secs(60));
+    collector.fail_with(&[500]);
+
+    let mut monitor = monitor(config);
+    let report = monitor.measure();
+    let error = monitor
+        .send(&ReportType::OnGoing, None, &report)
+        .unwrap_err();
+    assert!(error.to_string().contains("500"), "{error}");
+    assert!(error.to_string().contains("Mock failure"), "{error}");
+
+    monitor.send(&ReportType::OnGoing, None, &report).unwrap();
+    assert_eq!(collector.requests().len(), 2);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reporting_recovers_after_failures() {
+    let collector = MockCollector::start();
+    let (config, dir) = config("recovers", &collector, Duration::from_millis(100));
+    let data_path = config.data_path.clone();
+    collector.fail_with(&[500, 503]);
+
+    // The monitor keeps running, and reports again each period, after a report fails
+    let handle = monitor(config).start().unwrap();
+    wait_for_requests(&collector, 3);
+    handle.stop().unwrap();
+
+    let requests = collector.requests();
+    assert!(requests.len() >= 4);
+    assert!(requests[..3].iter().all(|r| r.path == "/report/ongoing"));
+
+    let state: MonitorState =
+        serde_json::from_str(&fs::read_to_string(state_file_path(&data_path)).unwrap()).unwrap();
+    let last_send = state.last_send.unwrap();
+    assert_eq!(last_send.report_type, "Stop");
+    assert!(last_send.success);
+    assert_eq!(state.consecutive_failures, 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn stop_report_failure_is_returned() {
+    let collector = MockCollector::start();
+    // A long period, so the only report sent is the Stop report
+    let (config, dir) = config("shutdown", &collector, Duration::from_secs(60));
+    let data_path = config.data_path.clone();
+    collector.fail_with(&[503]);
+
+    let handle = monitor(config).start().unwrap();
+    let error = handle.stop().unwrap_err();
+    assert!(error.to_string().contains("503"), "{error}");
+
+    let requests = collector.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/report/stop");
+
+    let state: MonitorState =
+        serde_json::from_str(&fs::read_to_string(state_file_path(&data_path)).unwrap()).unwrap();
+    assert!(!state.last_send.unwrap().success);
+    assert_eq!(state.consecutive_failures, 1);
+    assert_eq!(state.next_report, None);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,107 @@
+//! End-to-end tests of `wimon config check`, checking the config files and sending a test report
+//! to a local mock collector
+
+mod mock_collector;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use mock_collector::MockCollector;
+
+const DEVICE_ID: &str = "check-device";
+
+const TIMELINE: &str = r#"
+[[steps]]
+ssid = "MyWifi"
+signal_dbm = -55
+"#;
+
+// Write a config with an explicit device ID to a new directory for the test `name`, returning
+// the path of the config file
+fn config_file(name: &str, collector: &MockCollector) -> PathBuf {
+    let settings = format!("\n[device]\nid_strategy = \"Explicit\"\nid = \"{DEVICE_ID}\"\n");
+    collector.config_file(name, TIMELINE, &settings)
+}
+
+// Run `wimon config check` on the config file
+fn check(path: &PathBuf) -> (Output, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_wimon"))
+        .args(["config", "check"])
+        .arg(path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    (output, stdout)
+}
+
+#[test]
+fn check_sends_a_test_report() {
+    let collector = MockCollector::start();
+    let path = config_file("report", &collector);
+
+    let (output, stdout) = check(&path);
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("Config: OK"), "{stdout}");
+    assert!(
+        stdout.contains("Collector address: OK (127.0.0.1:"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("Test report: OK"), "{stdout}");
+
+    // Followed by a Stop report, so the collector doesn't expect another one
+    let requests = collector.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].path, "/report/ongoing");
+    assert_eq!(requests[1].path, "/report/stop");
+    for request in &requests {
+        assert_eq!(request.query("device_id"), Some(DEVICE_ID));
+        assert_eq!(request.query("reason"), Some("check"));
+    }
+    // Without the measurements, so only the collector is checked
+    assert!(!requests[0].body.contains("MyWifi"), "{}", requests[0].body);
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn check_fails_if_the_collector_does() {
+    let collector = MockCollector::start();
+    let path = config_file("failure", &collector);
+
+    collector.fail_with(&[500]);
+    let (output, stdout) = check(&path);
+    assert!(!output.status.success());
+    assert!(stdout.contains("Test report: FAILED"), "{stdout}");
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn check_finds_errors_in_the_files() {
+    let collector = MockCollector::start();
+    let path = config_file("errors", &collector);
+    fs::write(
+        path.with_file_name("ssid.toml"),
+        "[[networks]]\nssid = \"Home\"\npassword = \"short\"\n",
+    )
+    .unwrap();
+
+    let (output, stdout) = check(&path);
+    assert!(!output.status.success());
+    assert!(stdout.contains("Config: OK"), "{stdout}");
+    assert!(
+        stdout.contains("SSID file: FAILED") && stdout.contains("'Home' needs a password"),
+        "{stdout}"
+    );
+
+    // No test report is sent with a config that is not valid
+    let sent = collector.requests().len();
+    fs::write(&path, "[report]\nperiod_seconds = 0\n").unwrap();
+    let (output, stdout) = check(&path);
+    assert!(!output.status.success());
+    assert!(stdout.contains("Config: FAILED"), "{stdout}");
+    assert_eq!(collector.requests().len(), sent);
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,250 @@
+//! An in-process mock of collectr's `/report/:type` and `/config` routes, that records the
+//! requests it receives and responds with configurable HTTP status codes and device config,
+//! for end-to-end tests of wimon
+
+// Each of the tests uses only some of the mock
+#![allow(dead_code)]
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A request received by the [MockCollector]
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub content_type: Option<String>,
+    pub if_none_match: Option<String>,
+    pub body: String,
+}
+
+impl Request {
+    /// The decoded value of a query parameter
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The decoded value of a field of the form in the body
+    pub fn form(&self, name: &str) -> Option<String> {
+        parse_pairs(&self.body)
+            .into_iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    requests: Vec<Request>,
+    /// Status codes to respond with, in order, before responding with 200 again
+    statuses: VecDeque<u16>,
+    /// The config served by `/config`, which responds with 404 if there is none
+    config: Option<String>,
+}
+
+/// A mock collector listening on a random local port, until it is dropped
+pub struct MockCollector {
+    port: u16,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl MockCollector {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let shared = Arc::new(Mutex::new(Shared::default()));
+
+        let state = Arc::downgrade(&shared);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                // Stop serving once the MockCollector has been dropped
+                let Some(shared) = state.upgrade() else {
+                    break;
+                };
+                if let Ok(stream) = stream {
+                    handle(stream, &shared);
+                }
+            }
+        });
+
+        MockCollector { port, shared }
+    }
+
+    /// The base_url to configure wimon with
+    pub fn base_url(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.port)
+    }
+
+    /// Write a config that reports to this collector, with the history disabled and the
+    /// measurements simulated by `timeline`, to a new directory for the test `name`, returning
+    /// the path of the config file. `settings` follow the `base_url` in the `[report]` section,
+    /// and can start other sections.
+    pub fn config_file(&self, name: &str, timeline: &str, settings: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wimon-test-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("timeline.toml"), timeline).unwrap();
+        let path = dir.join("monitor.toml");
+        std::fs::write(
+            &path,
+            format!(
+                "[report]\nbase_url = \"{}\"\n{settings}\n[history]\nenabled = false\n\n\
+                 [simulation]\ntimeline_file = \"timeline.toml\"\n",
+                self.base_url()
+            ),
+        )
+        .unwrap();
+        path
+    }
+
+    /// Respond to the next requests with these status codes
+    pub fn fail_with(&self, statuses: &[u16]) {
+        self.shared
+            .lock()
+            .unwrap()
+            .statuses
+            .extend(statuses.iter().copied());
+    }
+
+    /// Serve this config to devices, or none
+    pub fn serve_config(&self, config: Option<&str>) {
+        self.shared.lock().unwrap().config = config.map(str::to_string);
+    }
+
+    /// The requests received so far
+    pub fn requests(&self) -> Vec<Request> {
+        self.shared.lock().unwrap().requests.clone()
+    }
+}
+
+// Read a request, record it, and respond to it
+fn handle(stream: TcpStream, shared: &Mutex<Shared>) {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    let mut words = request_line.split_whitespace();
+    let method = words.next().unwrap_or_default().to_string();
+    let target = words.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut content_length = 0;
+    let mut content_type = None;
+    let mut if_none_match = None;
+    let mut expect_continue = false;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            match name.to_ascii_lowercase().as_str() {
+                "content-length" => content_length = value.parse().unwrap_or(0),
+                "content-type" => content_type = Some(value.to_string()),
+                "if-none-match" => if_none_match = Some(value.to_string()),
+                "expect" => expect_continue = value.eq_ignore_ascii_case("100-continue"),
+                _ => {}
+            }
+        }
+    }
+    if expect_continue {
+        let _ = reader.get_mut().write_all(b"HTTP/1.1 100 Continue\r\n\r\n");
+    }
+    let mut body = vec![0; content_length];
+    if reader.read_exact(&mut body).is_err() {
+        return;
+    }
+
+    let (status, config) = {
+        let mut shared = shared.lock().unwrap();
+        shared.requests.push(Request {
+            method,
+            path: path.to_string(),
+            query: parse_pairs(query),
+            content_type,
+            if_none_match: if_none_match.clone(),
+            body: String::from_utf8_lossy(&body).into_owned(),
+        });
+        (
+            shared.statuses.pop_front().unwrap_or(200),
+            shared.config.clone(),
+        )
+    };
+
+    let mut headers = String::new();
+    let response_body = match (status, path, config) {
+        (200, "/config", None) => return respond(reader.get_mut(), 404, "", "No config"),
+        (200, "/config", Some(config)) => {
+            let etag = etag(&config);
+            if if_none_match.as_ref() == Some(&etag) {
+                return respond(reader.get_mut(), 304, &format!("ETag: {etag}\r\n"), "");
+            }
+            headers = format!("ETag: {etag}\r\n");
+            config
+        }
+        (200, _, _) => "TimeStamp: 0 Device ID: mock State: Reporting".to_string(),
+        _ => "Mock failure".to_string(),
+    };
+    respond(reader.get_mut(), status, &headers, &response_body);
+}
+
+fn respond(stream: &mut TcpStream, status: u16, headers: &str, body: &str) {
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {status} Mock\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+}
+
+fn etag(config: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    config.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+// Parse "application/x-www-form-urlencoded" pairs, as used in query strings and form bodies
+fn parse_pairs(data: &str) -> Vec<(String, String)> {
+    data.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+fn decode(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
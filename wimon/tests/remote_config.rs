@@ -0,0 +1,130 @@
+//! End-to-end tests of wimon fetching its config from collectr's `/config` route, and of the
+//! monitor using a new config while running, against a local mock collector
+
+mod mock_collector;
+
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use config::{load_config_as, Identity, RemoteSettings, Source};
+use wimon::remote::RemoteConfig;
+use wimon::Monitor;
+
+use mock_collector::{MockCollector, Request};
+
+const DEVICE_ID: &str = "remote-device";
+
+const TIMELINE: &str = r#"
+[[steps]]
+ssid = "MyWifi"
+signal_dbm = -55
+"#;
+
+// Write a config with the remote config enabled to a new directory for the test `name`,
+// returning the path of the config file
+fn config_file(name: &str, collector: &MockCollector) -> PathBuf {
+    let settings = "period_seconds = 60\n\n[remote]\npoll_seconds = 1\n";
+    collector.config_file(name, TIMELINE, settings)
+}
+
+fn load(path: &PathBuf, remote: Option<&RemoteSettings>) -> config::Config {
+    load_config_as(Some(path), &[], Identity::of, remote)
+        .unwrap()
+        .config
+}
+
+// Wait for the collector to receive a request that matches
+fn wait_for(collector: &MockCollector, matches: impl Fn(&Request) -> bool) -> Option<Request> {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        if let Some(request) = collector.requests().into_iter().find(&matches) {
+            return Some(request);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    None
+}
+
+#[test]
+fn config_is_only_fetched_again_when_changed() {
+    let collector = MockCollector::start();
+    let path = config_file("etag", &collector);
+    let config = load(&path, None);
+    let mut remote = RemoteConfig::new(&config, &DEVICE_ID.to_string()).unwrap();
+
+    // No config for the device, so only the local config is used
+    let settings = remote.fetch().unwrap().unwrap();
+    assert_eq!(load(&path, Some(&settings)).period_duration.as_secs(), 60);
+    assert!(remote.fetch().unwrap().is_none());
+
+    collector.serve_config(Some("[report]\nperiod_seconds = 30\n"));
+    let settings = remote.fetch().unwrap().unwrap();
+    let layered = load_config_as(Some(&path), &[], Identity::of, Some(&settings)).unwrap();
+    assert_eq!(layered.config.period_duration.as_secs(), 30);
+    assert_eq!(
+        layered.source("report.period_seconds"),
+        Some(&Source::Remote(remote.url().to_string()))
+    );
+    assert!(remote.fetch().unwrap().is_none());
+
+    let requests = collector.requests();
+    assert!(requests.iter().all(|r| r.path == "/config"));
+    assert_eq!(requests[0].query("device_id"), Some(DEVICE_ID));
+    let (last, _) = requests.split_last().unwrap();
+    assert!(last.if_none_match.is_some());
+
+    // Settings that can't be set remotely are an error, and not fetched again
+    collector.serve_config(Some("[service]\nusername = \"root\"\n"));
+    assert!(remote.fetch().is_err());
+    assert!(remote.fetch().unwrap().is_none());
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn collector_unreachable_is_an_error() {
+    let collector = MockCollector::start();
+    let path = config_file("unreachable", &collector);
+    let config = load(&path, None);
+    let mut remote = RemoteConfig::new(&config, &DEVICE_ID.to_string()).unwrap();
+
+    collector.fail_with(&[500]);
+    assert!(remote.fetch().is_err());
+    drop(collector);
+    assert!(remote.fetch().is_err());
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn monitor_uses_changed_config() {
+    let collector = MockCollector::start();
+    let path = config_file("watch", &collector);
+    let config = load(&path, None);
+    let remote = RemoteConfig::new(&config, &DEVICE_ID.to_string()).unwrap();
+
+    let handle = Monitor::builder(config)
+        .device_id(DEVICE_ID)
+        .build()
+        .unwrap()
+        .start()
+        .unwrap();
+    let load_path = path.clone();
+    remote
+        .watch(handle.reconfigurer(), move |settings| {
+            Ok(load_config_as(Some(&load_path), &[], Identity::of, settings)?.config)
+        })
+        .unwrap();
+
+    collector.serve_config(Some("[report]\nperiod_seconds = 30\n"));
+    // A report is sent straight away with the new config, so the collector knows its period
+    let report = wait_for(&collector, |r| r.query("reason") == Some("config"));
+    handle.stop().unwrap();
+    let report = report.expect("No report sent after the config changed");
+    assert_eq!(report.path, "/report/ongoing");
+    assert_eq!(report.query("period"), Some("30"));
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}